@@ -0,0 +1,247 @@
+use semver::{Prerelease, Version};
+use serde::Deserialize;
+use std::fmt;
+
+use crate::grouping::{breaking_changes, parse_conventional};
+use crate::Commit;
+
+/// Which part of the version a release bumps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BumpKind {
+    Major,
+    Minor,
+    Patch,
+    /// The next `-rc.N` release candidate
+    Rc,
+}
+
+impl fmt::Display for BumpKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BumpKind::Major => "major",
+            BumpKind::Minor => "minor",
+            BumpKind::Patch => "patch",
+            BumpKind::Rc => "rc",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The smallest bump an automatically detected release makes
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MinBump {
+    /// Commits without features, fixes or breaking changes don't make a release
+    None,
+    /// Every release bumps at least the patch version
+    #[default]
+    Patch,
+    /// Every release bumps at least the minor version
+    Minor,
+}
+
+/// Where the notes of a final release start when it finalizes a prerelease
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesSince {
+    /// The last prerelease, since earlier commits are in the prereleases' sections
+    #[default]
+    Prerelease,
+    /// The last final release, listing every commit of the prereleases again
+    Release,
+}
+
+/// The version after the bump, without any build metadata. Bumping a
+/// prerelease by the part it is a prerelease of releases it, so a patch bump
+/// of `1.2.4-rc.2` gives `1.2.4` and a major bump of `2.0.0-rc.1` gives `2.0.0`.
+pub fn bump(version: &Version, kind: BumpKind) -> Version {
+    let mut bumped = Version::new(version.major, version.minor, version.patch);
+    let is_prerelease = !version.pre.is_empty();
+
+    match kind {
+        BumpKind::Major => {
+            if !(is_prerelease && version.minor == 0 && version.patch == 0) {
+                bumped.major += 1;
+                bumped.minor = 0;
+                bumped.patch = 0;
+            }
+        }
+        BumpKind::Minor => {
+            if !(is_prerelease && version.patch == 0) {
+                bumped.minor += 1;
+                bumped.patch = 0;
+            }
+        }
+        BumpKind::Patch => {
+            if !is_prerelease {
+                bumped.patch += 1;
+            }
+        }
+        BumpKind::Rc => {
+            // Release candidates count up, and the first one starts the next patch
+            let number = match rc_number(&version.pre) {
+                Some(number) => number + 1,
+                None => {
+                    if !is_prerelease {
+                        bumped.patch += 1;
+                    }
+                    1
+                }
+            };
+            bumped.pre = Prerelease::new(&format!("rc.{number}")).unwrap();
+        }
+    }
+
+    bumped
+}
+
+/// The bump the commits call for, but at least the minimum bump. `None` when
+/// the commits don't call for a release and the minimum bump is `none`.
+pub fn detect_bump(commits: &[Commit], min_bump: MinBump) -> Option<BumpKind> {
+    let has_type = |types: &[&str]| {
+        commits.iter().any(|commit| {
+            parse_conventional(commit.msg).is_some_and(|conventional| {
+                types
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(conventional.commit_type))
+            })
+        })
+    };
+
+    let detected = if !breaking_changes(commits).is_empty() {
+        Some(BumpKind::Major)
+    } else if has_type(&["feat"]) {
+        Some(BumpKind::Minor)
+    } else if has_type(&["fix", "perf"]) {
+        Some(BumpKind::Patch)
+    } else {
+        None
+    };
+
+    match (detected, min_bump) {
+        (Some(BumpKind::Major), _) => Some(BumpKind::Major),
+        (_, MinBump::Minor) => Some(BumpKind::Minor),
+        (None, MinBump::Patch) => Some(BumpKind::Patch),
+        (detected, _) => detected,
+    }
+}
+
+fn rc_number(pre: &Prerelease) -> Option<u64> {
+    pre.as_str().strip_prefix("rc.")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bumped(version: &str, kind: BumpKind) -> String {
+        bump(&Version::parse(version).unwrap(), kind).to_string()
+    }
+
+    #[test]
+    fn major_bump() {
+        assert_eq!(bumped("1.2.3", BumpKind::Major), "2.0.0");
+        assert_eq!(bumped("0.9.0", BumpKind::Major), "1.0.0");
+        assert_eq!(bumped("1.2.3+ci.42", BumpKind::Major), "2.0.0");
+    }
+
+    #[test]
+    fn major_bump_of_a_prerelease() {
+        // A prerelease of a major version is released by the major bump
+        assert_eq!(bumped("2.0.0-rc.1", BumpKind::Major), "2.0.0");
+        assert_eq!(bumped("1.2.0-rc.1", BumpKind::Major), "2.0.0");
+        assert_eq!(bumped("1.2.3-rc.1", BumpKind::Major), "2.0.0");
+    }
+
+    #[test]
+    fn minor_bump() {
+        assert_eq!(bumped("1.2.3", BumpKind::Minor), "1.3.0");
+        assert_eq!(bumped("1.0.0", BumpKind::Minor), "1.1.0");
+    }
+
+    #[test]
+    fn minor_bump_of_a_prerelease() {
+        assert_eq!(bumped("1.3.0-rc.2", BumpKind::Minor), "1.3.0");
+        assert_eq!(bumped("2.0.0-rc.1", BumpKind::Minor), "2.0.0");
+        assert_eq!(bumped("1.2.3-rc.1", BumpKind::Minor), "1.3.0");
+    }
+
+    #[test]
+    fn patch_bump() {
+        assert_eq!(bumped("1.2.3", BumpKind::Patch), "1.2.4");
+        assert_eq!(bumped("1.2.3+ci.42", BumpKind::Patch), "1.2.4");
+    }
+
+    #[test]
+    fn patch_bump_of_a_prerelease() {
+        assert_eq!(bumped("1.2.4-rc.2", BumpKind::Patch), "1.2.4");
+        assert_eq!(bumped("1.3.0-rc.1", BumpKind::Patch), "1.3.0");
+        assert_eq!(bumped("2.0.0-beta", BumpKind::Patch), "2.0.0");
+    }
+
+    #[test]
+    fn rc_bump_of_a_release() {
+        assert_eq!(bumped("1.2.3", BumpKind::Rc), "1.2.4-rc.1");
+        assert_eq!(bumped("1.2.3+ci.42", BumpKind::Rc), "1.2.4-rc.1");
+    }
+
+    #[test]
+    fn rc_bump_of_a_release_candidate() {
+        assert_eq!(bumped("1.2.4-rc.1", BumpKind::Rc), "1.2.4-rc.2");
+        assert_eq!(bumped("2.0.0-rc.9", BumpKind::Rc), "2.0.0-rc.10");
+    }
+
+    #[test]
+    fn rc_bump_of_another_prerelease() {
+        assert_eq!(bumped("1.3.0-beta.2", BumpKind::Rc), "1.3.0-rc.1");
+        assert_eq!(bumped("1.3.0-rc.x", BumpKind::Rc), "1.3.0-rc.1");
+    }
+
+    fn commit(msg: &str) -> Commit<'_> {
+        Commit {
+            hash: "0123456",
+            author: "Test Author <author@example.com>",
+            msg,
+            wrapped: "",
+            body: "",
+        }
+    }
+
+    #[test]
+    fn detected_bump_follows_the_commits() {
+        let breaking = [commit("fix: a bug"), commit("feat!: drop the v1 API")];
+        let feature = [commit("fix: a bug"), commit("feat: add a flag")];
+        let fix = [commit("chore: tidy up"), commit("perf: faster parsing")];
+
+        for min_bump in [MinBump::None, MinBump::Patch] {
+            assert_eq!(detect_bump(&breaking, min_bump), Some(BumpKind::Major));
+            assert_eq!(detect_bump(&feature, min_bump), Some(BumpKind::Minor));
+            assert_eq!(detect_bump(&fix, min_bump), Some(BumpKind::Patch));
+        }
+    }
+
+    #[test]
+    fn minimum_bump_raises_the_detected_bump() {
+        let fix = [commit("fix: a bug")];
+        let breaking = [commit("feat!: drop the v1 API")];
+
+        assert_eq!(detect_bump(&fix, MinBump::Minor), Some(BumpKind::Minor));
+        assert_eq!(
+            detect_bump(&breaking, MinBump::Minor),
+            Some(BumpKind::Major)
+        );
+    }
+
+    #[test]
+    fn chore_only_range_under_each_minimum_bump() {
+        let chores = [
+            commit("chore: tidy up"),
+            commit("docs: fix a typo"),
+            commit("Update the lockfile"),
+        ];
+
+        assert_eq!(detect_bump(&chores, MinBump::None), None);
+        assert_eq!(detect_bump(&chores, MinBump::Patch), Some(BumpKind::Patch));
+        assert_eq!(detect_bump(&chores, MinBump::Minor), Some(BumpKind::Minor));
+    }
+}
@@ -0,0 +1,337 @@
+use regex::Regex;
+use semver::Version;
+
+/// A `# [version] - date` section of the changelog, located by line indices
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    /// The bracketed heading text, either a version or the Unreleased heading
+    pub version: String,
+    pub date: Option<String>,
+    /// Index of the heading line
+    pub heading: usize,
+    /// Index of the `[version]: ...` link line, if the section has one
+    pub link: Option<usize>,
+    /// Index one past the last line of the section
+    pub end: usize,
+}
+
+/// Splits the changelog lines into sections. Lines before the first section
+/// heading don't belong to any section.
+pub fn parse_sections<S: AsRef<str>>(lines: &[S]) -> Vec<Section> {
+    let heading_pattern = Regex::new(r"^# \[([^\]]+)\](?: - (.*))?$").unwrap();
+
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+
+        if let Some(cap) = heading_pattern.captures(line) {
+            if let Some(previous) = sections.last_mut() {
+                previous.end = index;
+            }
+
+            sections.push(Section {
+                version: cap[1].to_string(),
+                date: cap.get(2).map(|date| date.as_str().trim().to_string()),
+                heading: index,
+                link: None,
+                end: lines.len(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            if section.link.is_none() && line.starts_with(&format!("[{}]: ", section.version)) {
+                section.link = Some(index);
+            }
+        }
+    }
+
+    sections
+}
+
+/// Index of the first version heading written in the setext style, with the
+/// `[version] - date` text underlined by `=` or `-` instead of starting with `# `
+pub fn find_setext_heading<S: AsRef<str>>(lines: &[S]) -> Option<usize> {
+    let heading_pattern = Regex::new(r"^\[([^\]]+)\](?: - (.*))?$").unwrap();
+    let underline_pattern = Regex::new(r"^(=+|-+)\s*$").unwrap();
+
+    lines.windows(2).position(|pair| {
+        heading_pattern.is_match(pair[0].as_ref().trim_end())
+            && underline_pattern.is_match(pair[1].as_ref())
+    })
+}
+
+/// Puts exactly `spacing` blank lines before every section heading but the
+/// first, however many there were, so repeated releases don't pile up blank
+/// lines between sections
+pub fn normalize_section_spacing(lines: Vec<String>, spacing: usize) -> Vec<String> {
+    let headings: Vec<usize> = parse_sections(&lines)
+        .iter()
+        .skip(1)
+        .map(|section| section.heading)
+        .collect();
+
+    let mut normalized: Vec<String> = Vec::new();
+    let mut blank_lines = 0;
+    for (index, line) in lines.into_iter().enumerate() {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        let spacing = if headings.contains(&index) {
+            spacing
+        } else {
+            blank_lines
+        };
+        normalized.extend(std::iter::repeat_n(String::new(), spacing));
+        normalized.push(line);
+        blank_lines = 0;
+    }
+    normalized.extend(std::iter::repeat_n(String::new(), blank_lines));
+
+    normalized
+}
+
+pub fn find_section<'a>(sections: &'a [Section], version: &str) -> Option<&'a Section> {
+    sections.iter().find(|section| section.version == version)
+}
+
+/// Index one past the last line of the section's entries. That is the first
+/// link definition in the section, which is either the section's own link line
+/// or the start of a block of link definitions at the bottom of the changelog.
+pub fn section_body_end<S: AsRef<str>>(lines: &[S], section: &Section) -> usize {
+    let link_pattern = Regex::new(r"^\[([^\]]+)\]: ").unwrap();
+
+    (section.heading + 1..section.end)
+        .find(|&index| link_pattern.is_match(lines[index].as_ref()))
+        .unwrap_or(section.end)
+}
+
+/// Adds the entries of a freshly rendered section body that the section doesn't
+/// list yet, each under the same `## ` heading as in the body. Everything the
+/// section already has, including manual edits, is kept. Returns the merged
+/// changelog and the number of entries added.
+pub fn merge_entries<S: AsRef<str>>(
+    lines: &[S],
+    section: &Section,
+    body: &[String],
+    bullet: char,
+) -> (Vec<String>, usize) {
+    let entry_prefix = format!("{bullet} ");
+    let is_entry_line = |line: &str| line.starts_with(&entry_prefix) || line.starts_with("  ");
+
+    // Each entry of the body with the indented paragraphs below it
+    let mut entries: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    let mut title = None;
+    let mut in_entry = false;
+    for line in body {
+        if let Some(heading) = line.strip_prefix("## ") {
+            title = Some(heading);
+            in_entry = false;
+        } else if line.starts_with(&entry_prefix) {
+            entries.push((title, vec![line]));
+            in_entry = true;
+        } else if in_entry && (line.trim().is_empty() || line.starts_with("  ")) {
+            entries.last_mut().unwrap().1.push(line);
+        } else {
+            in_entry = false;
+        }
+    }
+
+    let mut merged: Vec<String> = lines.iter().map(|line| line.as_ref().to_string()).collect();
+    let mut end = section_body_end(lines, section);
+    let mut added = 0;
+
+    for (title, mut entry) in entries {
+        while entry.last().is_some_and(|line| line.trim().is_empty()) {
+            entry.pop();
+        }
+
+        let start = section.heading + 1;
+        if merged[start..end].iter().any(|line| line == entry[0]) {
+            continue;
+        }
+
+        // The lines of the entry's subsection, or of the whole section without headings
+        let subsection = match title {
+            Some(title) => merged[start..end]
+                .iter()
+                .position(|line| line.strip_prefix("## ") == Some(title))
+                .map(|index| start + index + 1),
+            None => Some(start),
+        };
+        let mut new_lines: Vec<String> = entry.iter().map(|line| line.to_string()).collect();
+
+        let insertion_index = match subsection {
+            Some(subsection) => {
+                let subsection_end = merged[subsection..end]
+                    .iter()
+                    .position(|line| line.starts_with("## "))
+                    .map_or(end, |index| subsection + index);
+                merged[subsection..subsection_end]
+                    .iter()
+                    .rposition(|line| is_entry_line(line))
+                    .map_or(subsection + 1, |index| subsection + index + 1)
+            }
+            None => {
+                // A new subsection goes after the last entry of the section
+                new_lines.splice(
+                    0..0,
+                    [
+                        String::new(),
+                        format!("## {}", title.unwrap()),
+                        String::new(),
+                    ],
+                );
+                merged[start..end]
+                    .iter()
+                    .rposition(|line| is_entry_line(line))
+                    .map_or(start, |index| start + index + 1)
+            }
+        };
+
+        end += new_lines.len();
+        merged.splice(insertion_index..insertion_index, new_lines);
+        added += 1;
+    }
+
+    // Entries added to a section without any, like a planned one, would
+    // otherwise run into the link line
+    if added > 0 && end < merged.len() && !merged[end - 1].trim().is_empty() {
+        merged.insert(end, String::new());
+    }
+
+    (merged, added)
+}
+
+/// Where the link line for a new version goes in a block of link definitions
+/// starting at `start`. Links are kept newest first, so it goes before the
+/// first link to an older version, or after the last link of the block.
+pub fn link_insertion_index<S: AsRef<str>>(lines: &[S], start: usize, version: &str) -> usize {
+    let link_pattern = Regex::new(r"^\[([^\]]+)\]: ").unwrap();
+    let new_version = Version::parse(version).ok();
+
+    let mut insertion_index = start + 1;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let line = line.as_ref();
+
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(cap) = link_pattern.captures(line) else {
+            break;
+        };
+
+        let is_older = match (&new_version, Version::parse(&cap[1])) {
+            (Some(new_version), Ok(linked_version)) => linked_version < *new_version,
+            _ => false,
+        };
+        if is_older {
+            return index;
+        }
+        insertion_index = index + 1;
+    }
+
+    insertion_index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A changelog keeping every link definition in a block at the bottom
+    const BOTTOM_LINKS: &[&str] = &[
+        "# Changelog",
+        "",
+        "# [Unreleased]",
+        "",
+        "# [1.1.0] - 2024-02-01",
+        "",
+        "- Second release",
+        "",
+        "# [1.0.0] - 2024-01-01",
+        "",
+        "- First release",
+        "",
+        "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.1.0...HEAD",
+        "[1.1.0]: https://github.com/shipperstack/shipper/compare/1.0.0...1.1.0",
+        "[1.0.0]: https://github.com/shipperstack/shipper/tree/1.0.0",
+    ];
+
+    #[test]
+    fn link_of_a_new_version_goes_before_older_versions() {
+        assert_eq!(link_insertion_index(BOTTOM_LINKS, 12, "1.2.0"), 13);
+    }
+
+    #[test]
+    fn link_of_a_version_between_releases() {
+        assert_eq!(link_insertion_index(BOTTOM_LINKS, 12, "1.0.1"), 14);
+    }
+
+    #[test]
+    fn link_of_the_oldest_version_ends_the_block() {
+        assert_eq!(link_insertion_index(BOTTOM_LINKS, 12, "0.9.0"), 15);
+
+        let mut lines = BOTTOM_LINKS.to_vec();
+        lines.extend(["", "Made with shipper-release."]);
+        assert_eq!(link_insertion_index(&lines, 12, "0.9.0"), 15);
+    }
+
+    fn longest_blank_run(lines: &[String]) -> usize {
+        lines
+            .split(|line| !line.trim().is_empty())
+            .map(<[String]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn spacing_between_sections_is_collapsed() {
+        let lines: Vec<String> = [
+            "# Changelog",
+            "",
+            "# [Unreleased]",
+            "",
+            "",
+            "",
+            "",
+            "# [1.1.0] - 2024-02-01",
+            "",
+            "- Second release",
+            "# [1.0.0] - 2024-01-01",
+            "",
+            "- First release",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        for spacing in [1, 2] {
+            let normalized = normalize_section_spacing(lines.clone(), spacing);
+
+            assert!(longest_blank_run(&normalized) <= spacing);
+            for section in parse_sections(&normalized).iter().skip(1) {
+                let blank_lines = normalized[..section.heading]
+                    .iter()
+                    .rev()
+                    .take_while(|line| line.is_empty())
+                    .count();
+                assert_eq!(blank_lines, spacing, "before {}", section.version);
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_normalizing_does_not_pile_up_blank_lines() {
+        let lines: Vec<String> = ["# [Unreleased]", "", "", "", "# [1.0.0] - 2024-01-01", ""]
+            .map(String::from)
+            .to_vec();
+
+        let once = normalize_section_spacing(lines, 2);
+        let twice = normalize_section_spacing(once.clone(), 2);
+
+        assert_eq!(
+            once,
+            ["# [Unreleased]", "", "", "# [1.0.0] - 2024-01-01", ""]
+        );
+        assert_eq!(twice, once);
+    }
+}
@@ -0,0 +1,359 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::bump::{MinBump, NotesSince};
+use crate::grouping::{
+    default_commit_types, default_verb_sections, CommitType, GroupBy, SortOrder, VerbSection,
+};
+use crate::links::LinkStyle;
+
+// Optional per-repository settings live next to the changelog
+pub const CONFIG_FILE_NAME: &str = "shipper-release.toml";
+
+// Markdown list markers that entries can be written with
+pub const BULLET_MARKERS: &[char] = &['-', '*', '+'];
+
+// Placeholders available to each template
+const FOOTER_PLACEHOLDERS: &[&str] = &["version", "previous_version", "date"];
+const MESSAGE_PLACEHOLDERS: &[&str] = &["version", "date", "changes"];
+const COMPARE_URL_PLACEHOLDERS: &[&str] = &["from", "to"];
+const COMMIT_URL_PLACEHOLDERS: &[&str] = &["hash"];
+const ISSUE_URL_PLACEHOLDERS: &[&str] = &["number"];
+const RELEASE_FILE_PLACEHOLDERS: &[&str] = &["version"];
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Template appended to the end of each newly generated release section.
+    /// Supports the `{version}`, `{previous_version}` and `{date}` placeholders.
+    pub footer: Option<String>,
+    /// Template for the release commit message
+    pub commit_message: String,
+    /// Template for the release tag message. When set, `push` creates an
+    /// annotated tag instead of a lightweight one.
+    pub tag_message: Option<String>,
+    /// How commits are grouped into sections in the generated changelog
+    pub group_by: GroupBy,
+    /// Commit types and the section each is listed under, in section order
+    pub commit_types: Vec<CommitType>,
+    /// Leading verbs and the section each is listed under when grouping by
+    /// verb, in section order
+    pub verb_sections: Vec<VerbSection>,
+    /// Section for commits that don't match any of the commit types or verbs
+    pub default_section: String,
+    /// Order of the entries within each section
+    pub sort: SortOrder,
+    /// Text of the `# [Unreleased]` heading
+    pub unreleased_heading: String,
+    /// Anchor of the `[Unreleased]: ...` link line. Defaults to the heading text.
+    pub unreleased_anchor: Option<String>,
+    /// Always sign the release commit and tag, as if `push --sign` was passed
+    pub sign: bool,
+    /// GPG key used for signing. Defaults to git's user.signingkey.
+    pub signing_key: Option<String>,
+    /// GPG program used for signing, passed to git as gpg.program
+    pub gpg_program: Option<String>,
+    /// Remotes the release is pushed to. Defaults to git's default remote.
+    pub remotes: Vec<String>,
+    /// What the link line at the end of each release section points to
+    pub link_style: LinkStyle,
+    /// Check crates.io once a day for a newer shipper-release
+    pub update_check: bool,
+    /// Pattern of the leading ticket key removed by `--strip-ticket-prefix`
+    pub ticket_pattern: String,
+    /// Base URL that ticket keys are appended to when linking stripped tickets
+    pub ticket_url: Option<String>,
+    /// Footer keywords whose issue references `--footer-issues` appends to entries
+    pub issue_footers: Vec<String>,
+    /// The `owner/name` of the GitHub repository that links point at
+    pub repository: Option<String>,
+    /// Template for compare links, with the `{from}` and `{to}` placeholders.
+    /// Defaults to GitHub's compare page.
+    pub compare_url_template: Option<String>,
+    /// Template for commit links, with the `{hash}` placeholder
+    pub commit_url_template: Option<String>,
+    /// Template for issue links, with the `{number}` placeholder. When set,
+    /// `#123` references in entries are linked.
+    pub issue_url_template: Option<String>,
+    /// Prefix of the release tags, e.g. `v` for tags like `v1.2.3`
+    pub tag_prefix: String,
+    /// Shell command that must succeed before `push` tags the release commit
+    pub verify_command: Option<String>,
+    /// Number of blank lines between sections when a section is inserted
+    pub section_spacing: usize,
+    /// Where the notes start when a final release finalizes a prerelease
+    pub finalize_notes_since: NotesSince,
+    /// List marker that generated entries start with
+    pub bullet: char,
+    /// Smallest bump of `generate --bump auto`
+    pub min_bump: MinBump,
+    /// Path of a file that `generate` also writes the new release section to,
+    /// with the `{version}` placeholder, e.g. `changelogs/{version}.md`
+    pub release_file: Option<String>,
+    /// Path of the git executable to run. Defaults to `git` from the `PATH`.
+    pub git_path: Option<String>,
+    /// Conventional commit scopes that `generate` accepts without a warning.
+    /// Any scope is accepted when empty.
+    pub allowed_scopes: Vec<String>,
+    /// Files that `push` stages for the release commit along with the files
+    /// `generate` writes, e.g. `Cargo.lock`
+    pub extra_staged_files: Vec<String>,
+    /// Check that the tag the release starts at points at the same commit on
+    /// the remote, which needs network access
+    pub check_remote_tag: bool,
+    /// Emoji or other text put before each section heading, by heading, e.g.
+    /// `Features = "✨"`
+    pub section_prefixes: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            footer: None,
+            commit_message: String::from("release: {version}\n\n{changes}"),
+            tag_message: None,
+            group_by: GroupBy::None,
+            commit_types: default_commit_types(),
+            verb_sections: default_verb_sections(),
+            default_section: String::from("Other"),
+            sort: SortOrder::Git,
+            unreleased_heading: String::from("Unreleased"),
+            unreleased_anchor: None,
+            sign: false,
+            signing_key: None,
+            gpg_program: None,
+            remotes: Vec::new(),
+            link_style: LinkStyle::Compare,
+            update_check: false,
+            ticket_pattern: String::from(r"^[A-Z]+-\d+:?\s*"),
+            ticket_url: None,
+            issue_footers: vec![
+                String::from("Closes"),
+                String::from("Fixes"),
+                String::from("Refs"),
+            ],
+            repository: None,
+            compare_url_template: None,
+            commit_url_template: None,
+            issue_url_template: None,
+            tag_prefix: String::new(),
+            verify_command: None,
+            section_spacing: 2,
+            finalize_notes_since: NotesSince::Prerelease,
+            bullet: '-',
+            min_bump: MinBump::Patch,
+            release_file: None,
+            git_path: None,
+            allowed_scopes: Vec::new(),
+            extra_staged_files: Vec::new(),
+            check_remote_tag: false,
+            section_prefixes: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file from the current directory, falling back to the
+    /// defaults if the file does not exist
+    pub fn load() -> Result<Config, String> {
+        if !Path::new(CONFIG_FILE_NAME).exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(CONFIG_FILE_NAME)
+            .map_err(|e| format!("Failed to read {CONFIG_FILE_NAME}: {e}"))?;
+
+        let config: Config = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {CONFIG_FILE_NAME}: {e}"))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn unreleased_anchor(&self) -> &str {
+        self.unreleased_anchor
+            .as_deref()
+            .unwrap_or(&self.unreleased_heading)
+    }
+
+    /// Name of the release tag of the version
+    pub fn tag_name(&self, version: &str) -> String {
+        format!("{}{version}", self.tag_prefix)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(footer) = &self.footer {
+            validate_template("footer", footer, FOOTER_PLACEHOLDERS)?;
+        }
+
+        validate_template("commit_message", &self.commit_message, MESSAGE_PLACEHOLDERS)?;
+        if self
+            .commit_message
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .is_empty()
+        {
+            return Err(String::from(
+                "The commit_message template must start with a non-empty subject line!",
+            ));
+        }
+
+        if let Some(tag_message) = &self.tag_message {
+            validate_template("tag_message", tag_message, MESSAGE_PLACEHOLDERS)?;
+            if tag_message.trim().is_empty() {
+                return Err(String::from("The tag_message template must not be empty!"));
+            }
+        }
+
+        if self.default_section.trim().is_empty()
+            || self
+                .commit_types
+                .iter()
+                .any(|commit_type| commit_type.section.trim().is_empty())
+        {
+            return Err(String::from(
+                "Commit type section headings must not be empty!",
+            ));
+        }
+
+        if self.verb_sections.iter().any(|verb_section| {
+            verb_section.verb.trim().is_empty() || verb_section.section.trim().is_empty()
+        }) {
+            return Err(String::from(
+                "Verbs and their section headings must not be empty!",
+            ));
+        }
+
+        if self.unreleased_heading.trim().is_empty() || self.unreleased_anchor().trim().is_empty() {
+            return Err(String::from(
+                "The Unreleased heading and anchor must not be empty!",
+            ));
+        }
+
+        if let Err(e) = Regex::new(&self.ticket_pattern) {
+            return Err(format!("The ticket_pattern is not a valid regex: {e}"));
+        }
+
+        if let Some(repository) = &self.repository {
+            if repository.split('/').count() < 2 || repository.split('/').any(str::is_empty) {
+                return Err(format!(
+                    "The repository {repository} must be of the form owner/name!"
+                ));
+            }
+        }
+
+        if !BULLET_MARKERS.contains(&self.bullet) {
+            return Err(format!(
+                "The bullet {} must be one of: {}",
+                self.bullet,
+                BULLET_MARKERS
+                    .iter()
+                    .map(char::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        if let Some((section, _)) = self
+            .section_prefixes
+            .iter()
+            .find(|(_, prefix)| prefix.trim().is_empty() || prefix.contains('\n'))
+        {
+            return Err(format!(
+                "The section prefix for {section} must be a non-empty single line!"
+            ));
+        }
+
+        if self.section_spacing == 0 {
+            return Err(String::from(
+                "The section_spacing must be at least one blank line!",
+            ));
+        }
+
+        if self.tag_prefix.contains(char::is_whitespace) {
+            return Err(format!(
+                "The tag_prefix {:?} must not contain whitespace!",
+                self.tag_prefix
+            ));
+        }
+
+        if let Some(release_file) = &self.release_file {
+            validate_template("release_file", release_file, RELEASE_FILE_PLACEHOLDERS)?;
+            if !release_file.contains("{version}") {
+                return Err(String::from(
+                    "The release_file template must contain the {version} placeholder!",
+                ));
+            }
+        }
+
+        let url_templates = [
+            (
+                "compare_url_template",
+                &self.compare_url_template,
+                COMPARE_URL_PLACEHOLDERS,
+            ),
+            (
+                "commit_url_template",
+                &self.commit_url_template,
+                COMMIT_URL_PLACEHOLDERS,
+            ),
+            (
+                "issue_url_template",
+                &self.issue_url_template,
+                ISSUE_URL_PLACEHOLDERS,
+            ),
+        ];
+        for (name, template, placeholders) in url_templates {
+            if let Some(template) = template {
+                validate_template(name, template, placeholders)?;
+                for placeholder in placeholders {
+                    if !template.contains(&format!("{{{placeholder}}}")) {
+                        return Err(format!(
+                            "The {name} template must contain the {{{placeholder}}} placeholder!"
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the template only uses the given placeholders
+fn validate_template(name: &str, template: &str, placeholders: &[&str]) -> Result<(), String> {
+    let pattern = Regex::new(r"\{([A-Za-z_]+)\}").unwrap();
+
+    for cap in pattern.captures_iter(template) {
+        if !placeholders.contains(&&cap[1]) {
+            return Err(format!(
+                "Unknown placeholder {{{}}} in the {name} template! Valid placeholders are: {}",
+                &cap[1],
+                placeholders
+                    .iter()
+                    .map(|placeholder| format!("{{{placeholder}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces every `{key}` placeholder in the template with its value
+pub fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = template.to_string();
+
+    for (key, value) in values {
+        rendered = rendered.replace(&format!("{{{key}}}"), value);
+    }
+
+    rendered
+}
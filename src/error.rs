@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// Why a command failed. Each class has its own exit code, so wrapper scripts
+/// can tell them apart.
+#[derive(Debug)]
+pub enum Error {
+    /// Any failure without a more specific class
+    Other(String),
+    /// The arguments are invalid in a way clap doesn't catch
+    Usage(String),
+    /// Not running in the shipper repository
+    NotInRepository(String),
+    /// A git command failed
+    Git(String),
+    /// There are no commits since the last release
+    NothingToRelease(String),
+}
+
+impl Error {
+    /// Exit code of the failure class. Clap exits with 2 for its own usage errors too.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Other(_) => 1,
+            Error::Usage(_) => 2,
+            Error::NotInRepository(_) => 3,
+            Error::Git(_) => 4,
+            Error::NothingToRelease(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Other(message)
+            | Error::Usage(message)
+            | Error::NotInRepository(message)
+            | Error::Git(message)
+            | Error::NothingToRelease(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
@@ -0,0 +1,65 @@
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+use crate::error::Error;
+use crate::logging::{traced_output, traced_query};
+
+static PROGRAM: OnceLock<String> = OnceLock::new();
+
+/// Sets the git executable that every git command runs. Only the first call
+/// takes effect, so it must happen before git is first used.
+pub fn set_program(program: String) {
+    let _ = PROGRAM.set(program);
+}
+
+/// The configured git executable, `git` from the `PATH` by default
+pub fn program() -> &'static str {
+    PROGRAM.get().map_or("git", String::as_str)
+}
+
+/// A command running the configured git executable
+pub fn command() -> Command {
+    Command::new(program())
+}
+
+/// Fails with a clear message if the configured git executable can't be run
+pub fn check_program() -> Result<(), String> {
+    match command().arg("--version").output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "The git executable {} exited with {} when asked for its version!",
+            program(),
+            output.status
+        )),
+        Err(e) => Err(format!(
+            "Unable to run the git executable {}: {e}",
+            program()
+        )),
+    }
+}
+
+/// Runs the git command, capturing its output. A non-zero exit status is an
+/// error with the failure message followed by what git printed to stderr.
+pub fn run_git(command: &mut Command, failure: &str) -> Result<Output, Error> {
+    let output = traced_output(command)
+        .map_err(|e| Error::Git(format!("{failure} Unable to run {}: {e}", program())))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Git(if stderr.trim().is_empty() {
+            failure.to_string()
+        } else {
+            format!("{failure}\n{}", stderr.trim_end())
+        }));
+    }
+
+    Ok(output)
+}
+
+/// Runs a git command that answers a question with its exit status, like
+/// `git rev-parse --verify`, capturing its output. Only failing to run git is
+/// an error, so the caller checks the exit status.
+pub fn query_git(command: &mut Command, failure: &str) -> Result<Output, Error> {
+    traced_query(command)
+        .map_err(|e| Error::Git(format!("{failure} Unable to run {}: {e}", program())))
+}
@@ -0,0 +1,512 @@
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::Commit;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// List every commit in git order without any headings
+    #[default]
+    None,
+    /// Group commits by their conventional commit type
+    Type,
+    /// Group commits by the leading verb of their subject, e.g. "Add" or "Fix"
+    Verb,
+}
+
+/// Order of the entries within each section
+#[derive(Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Oldest commit first, as listed by git
+    #[default]
+    Git,
+    /// Alphabetically by entry text, ignoring case. Ties keep git order.
+    Alphabetical,
+}
+
+/// Maps a conventional commit type to the section heading it is listed under
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CommitType {
+    #[serde(rename = "type")]
+    pub name: String,
+    pub section: String,
+}
+
+impl CommitType {
+    fn new(name: &str, section: &str) -> Self {
+        CommitType {
+            name: String::from(name),
+            section: String::from(section),
+        }
+    }
+}
+
+pub fn default_commit_types() -> Vec<CommitType> {
+    vec![
+        CommitType::new("feat", "Features"),
+        CommitType::new("fix", "Bug Fixes"),
+        CommitType::new("perf", "Performance"),
+        CommitType::new("refactor", "Refactoring"),
+        CommitType::new("docs", "Documentation"),
+    ]
+}
+
+/// Maps a leading verb of a commit subject to the section it is listed under
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VerbSection {
+    pub verb: String,
+    pub section: String,
+}
+
+impl VerbSection {
+    fn new(verb: &str, section: &str) -> Self {
+        VerbSection {
+            verb: String::from(verb),
+            section: String::from(section),
+        }
+    }
+}
+
+/// The Keep a Changelog sections for the most common verbs
+pub fn default_verb_sections() -> Vec<VerbSection> {
+    vec![
+        VerbSection::new("Add", "Added"),
+        VerbSection::new("Change", "Changed"),
+        VerbSection::new("Update", "Changed"),
+        VerbSection::new("Remove", "Removed"),
+        VerbSection::new("Fix", "Fixed"),
+    ]
+}
+
+/// A commit message following the conventional commits specification
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConventionalCommit<'a> {
+    pub commit_type: &'a str,
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub description: &'a str,
+}
+
+pub fn parse_conventional(msg: &str) -> Option<ConventionalCommit<'_>> {
+    let pattern = Regex::new(
+        r"(?x)
+            ^([A-Za-z]+)        # type
+            (?:\(([^)]*)\))?    # optional scope
+            (!)?                # optional breaking change marker
+            :\s+(.+)$           # description",
+    )
+    .unwrap();
+
+    let cap = pattern.captures(msg)?;
+
+    Some(ConventionalCommit {
+        commit_type: cap.get(1).unwrap().as_str(),
+        scope: cap.get(2).map(|scope| scope.as_str()),
+        breaking: cap.get(3).is_some(),
+        description: cap.get(4).unwrap().as_str(),
+    })
+}
+
+/// Extracts the description from a `BREAKING CHANGE:` footer in the commit body
+fn breaking_change_footer(body: &str) -> Option<String> {
+    let mut lines = body.lines().skip_while(|line| {
+        !line.starts_with("BREAKING CHANGE:") && !line.starts_with("BREAKING-CHANGE:")
+    });
+
+    let first_line = lines.next()?.split_once(':').unwrap().1.trim();
+
+    // The footer continues until the end of its paragraph
+    let description = std::iter::once(first_line)
+        .chain(
+            lines
+                .take_while(|line| !line.trim().is_empty())
+                .map(str::trim),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(description)
+}
+
+/// Issue numbers referenced by footers like `Closes #12` or `Refs: #3, #4`,
+/// for the given footer keywords, in order without duplicates
+pub fn footer_issues(keywords: &[String], body: &str) -> Vec<String> {
+    let issue_pattern = Regex::new(r"#(\d+)\b").unwrap();
+
+    let mut issues: Vec<String> = Vec::new();
+    for line in body.lines() {
+        let Some((keyword, references)) = line
+            .trim()
+            .split_once(|c: char| c == ':' || c.is_whitespace())
+        else {
+            continue;
+        };
+        if !keywords
+            .iter()
+            .any(|footer| footer.eq_ignore_ascii_case(keyword))
+        {
+            continue;
+        }
+
+        for cap in issue_pattern.captures_iter(references) {
+            if !issues.iter().any(|issue| issue == &cap[1]) {
+                issues.push(cap[1].to_string());
+            }
+        }
+    }
+
+    issues
+}
+
+/// Descriptions of every breaking change, either from a `type!:` subject or a
+/// `BREAKING CHANGE:` footer, in commit order
+pub fn breaking_changes(commits: &[Commit]) -> Vec<String> {
+    commits
+        .iter()
+        .filter_map(|commit| {
+            if let Some(description) = breaking_change_footer(commit.body) {
+                return Some(description);
+            }
+
+            parse_conventional(commit.msg)
+                .filter(|conventional| conventional.breaking)
+                .map(|conventional| conventional.description.to_string())
+        })
+        .collect()
+}
+
+/// Commits sharing a section heading. The title is `None` when grouping is disabled.
+pub struct Group<'a> {
+    pub title: Option<String>,
+    pub commits: Vec<Commit<'a>>,
+}
+
+/// Groups commits into sections. Sections follow the order of the configured
+/// commit types, followed by the default section for everything else. Within
+/// each section entries are ordered by the sort order, so the same commits
+/// always produce the same output.
+pub fn group_commits<'a>(
+    config: &Config,
+    sort: SortOrder,
+    commits: Vec<Commit<'a>>,
+) -> Vec<Group<'a>> {
+    let mut groups = match config.group_by {
+        GroupBy::None => vec![Group {
+            title: None,
+            commits,
+        }],
+        GroupBy::Type => group_by_type(config, commits),
+        GroupBy::Verb => group_by_verb(config, commits),
+    };
+
+    if sort == SortOrder::Alphabetical {
+        for group in &mut groups {
+            // `sort_by_cached_key` is stable, so equal entries keep git order
+            group
+                .commits
+                .sort_by_cached_key(|commit| entry_text(config, commit).to_lowercase());
+        }
+    }
+
+    groups
+}
+
+fn group_by_type<'a>(config: &Config, commits: Vec<Commit<'a>>) -> Vec<Group<'a>> {
+    let sections = config
+        .commit_types
+        .iter()
+        .map(|commit_type| &commit_type.section);
+
+    group_into_sections(config, sections, commits, |commit| {
+        let conventional = parse_conventional(commit.msg)?;
+        config
+            .commit_types
+            .iter()
+            .find(|commit_type| commit_type.name == conventional.commit_type)
+            .map(|commit_type| &commit_type.section)
+    })
+}
+
+fn group_by_verb<'a>(config: &Config, commits: Vec<Commit<'a>>) -> Vec<Group<'a>> {
+    let sections = config
+        .verb_sections
+        .iter()
+        .map(|verb_section| &verb_section.section);
+
+    group_into_sections(config, sections, commits, |commit| {
+        let first_word = commit.msg.split_whitespace().next()?.to_lowercase();
+        config
+            .verb_sections
+            .iter()
+            .find(|verb_section| verb_matches(&verb_section.verb, &first_word))
+            .map(|verb_section| &verb_section.section)
+    })
+}
+
+/// Whether the word is the verb, ignoring case and common inflections like
+/// "Adds" or "Fixed"
+fn verb_matches(verb: &str, word: &str) -> bool {
+    let verb = verb.to_lowercase();
+
+    word.strip_prefix(&verb)
+        .is_some_and(|suffix| ["", "s", "es", "d", "ed"].contains(&suffix))
+}
+
+/// Sorts commits into the sections in the given order, followed by the
+/// default section for commits without one. Empty sections are left out.
+fn group_into_sections<'a, 'c>(
+    config: &'c Config,
+    sections: impl Iterator<Item = &'c String>,
+    commits: Vec<Commit<'a>>,
+    section_of: impl Fn(&Commit) -> Option<&'c String>,
+) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for section in sections {
+        if !groups
+            .iter()
+            .any(|group| group.title.as_ref() == Some(section))
+        {
+            groups.push(Group {
+                title: Some(section.clone()),
+                commits: Vec::new(),
+            });
+        }
+    }
+    groups.push(Group {
+        title: Some(config.default_section.clone()),
+        commits: Vec::new(),
+    });
+
+    for commit in commits {
+        let section = section_of(&commit).unwrap_or(&config.default_section);
+
+        groups
+            .iter_mut()
+            .find(|group| group.title.as_ref() == Some(section))
+            .unwrap()
+            .commits
+            .push(commit);
+    }
+
+    groups.retain(|group| !group.commits.is_empty());
+
+    groups
+}
+
+/// Text of the changelog bullet for the commit. When grouped under a commit
+/// type's section, the type prefix is implied by the heading and is dropped.
+pub fn entry_text(config: &Config, commit: &Commit) -> String {
+    if config.group_by != GroupBy::Type {
+        return commit.msg.to_string();
+    }
+
+    let conventional = parse_conventional(commit.msg).filter(|conventional| {
+        config
+            .commit_types
+            .iter()
+            .any(|commit_type| commit_type.name == conventional.commit_type)
+    });
+
+    match conventional {
+        Some(ConventionalCommit {
+            scope: Some(scope),
+            description,
+            ..
+        }) if !scope.is_empty() => format!("**{scope}:** {description}"),
+        Some(conventional) => conventional.description.to_string(),
+        None => commit.msg.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit<'a>(msg: &'a str, body: &'a str) -> Commit<'a> {
+        Commit {
+            hash: "0123456",
+            author: "Test Author <author@example.com>",
+            msg,
+            wrapped: "",
+            body,
+        }
+    }
+
+    #[test]
+    fn breaking_change_from_the_type_marker() {
+        let commits = [
+            commit("feat(api)!: remove the v1 endpoints", ""),
+            commit("feat: add the v2 endpoints", ""),
+        ];
+
+        assert_eq!(
+            breaking_changes(&commits),
+            vec![String::from("remove the v1 endpoints")]
+        );
+    }
+
+    #[test]
+    fn breaking_change_from_the_footer() {
+        let commits = [
+            commit(
+                "fix: parse dates strictly",
+                "Dates used to be guessed.\n\nBREAKING CHANGE: dates without a year\nare rejected.\n\nRefs #12",
+            ),
+            commit(
+                "refactor: rename the config",
+                "BREAKING-CHANGE: the config file is shipper.toml",
+            ),
+        ];
+
+        assert_eq!(
+            breaking_changes(&commits),
+            vec![
+                String::from("dates without a year are rejected."),
+                String::from("the config file is shipper.toml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn footer_description_wins_over_the_subject() {
+        let commits = [commit(
+            "feat!: new output",
+            "BREAKING CHANGE: the output is JSON",
+        )];
+
+        assert_eq!(
+            breaking_changes(&commits),
+            vec![String::from("the output is JSON")]
+        );
+    }
+
+    #[test]
+    fn commits_without_breaking_changes() {
+        let commits = [
+            commit(
+                "feat: add a flag",
+                "Mentions a BREAKING CHANGE: in the middle",
+            ),
+            commit("Fix a typo", ""),
+        ];
+
+        assert!(breaking_changes(&commits).is_empty());
+    }
+
+    #[test]
+    fn verb_matches_its_inflections() {
+        let inflections = [
+            ("Add", ["add", "adds", "added"]),
+            ("Change", ["change", "changes", "changed"]),
+            ("Update", ["update", "updates", "updated"]),
+            ("Remove", ["remove", "removes", "removed"]),
+            ("Fix", ["fix", "fixes", "fixed"]),
+        ];
+
+        for (verb, words) in inflections {
+            for word in words {
+                assert!(verb_matches(verb, word), "{verb} should match {word}");
+            }
+        }
+    }
+
+    #[test]
+    fn verb_does_not_match_other_words() {
+        assert!(!verb_matches("Add", "address"));
+        assert!(!verb_matches("Fix", "fixture"));
+        assert!(!verb_matches("Update", "up"));
+        assert!(!verb_matches("Remove", "removing"));
+    }
+
+    #[test]
+    fn commits_are_grouped_by_verb() {
+        let config = Config {
+            group_by: GroupBy::Verb,
+            ..Config::default()
+        };
+        let commits = vec![
+            commit("Fixed the crash on startup", ""),
+            commit("Add a flag", ""),
+            commit("Removes the old parser", ""),
+            commit("Updated the docs", ""),
+            commit("Change the default port", ""),
+            commit("Refactor the parser", ""),
+            commit("adds tests", ""),
+        ];
+
+        let groups: Vec<(Option<String>, Vec<&str>)> =
+            group_commits(&config, SortOrder::Git, commits)
+                .into_iter()
+                .map(|group| {
+                    let msgs = group.commits.iter().map(|commit| commit.msg).collect();
+                    (group.title, msgs)
+                })
+                .collect();
+
+        assert_eq!(
+            groups,
+            [
+                (
+                    Some(String::from("Added")),
+                    vec!["Add a flag", "adds tests"]
+                ),
+                (
+                    Some(String::from("Changed")),
+                    vec!["Updated the docs", "Change the default port"]
+                ),
+                (
+                    Some(String::from("Removed")),
+                    vec!["Removes the old parser"]
+                ),
+                (
+                    Some(String::from("Fixed")),
+                    vec!["Fixed the crash on startup"]
+                ),
+                (Some(String::from("Other")), vec!["Refactor the parser"]),
+            ]
+        );
+    }
+
+    fn issue_footers() -> Vec<String> {
+        ["Closes", "Fixes", "Refs"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn closes_footer() {
+        assert_eq!(
+            footer_issues(&issue_footers(), "Some context.\n\nCloses #12"),
+            ["12"]
+        );
+    }
+
+    #[test]
+    fn fixes_footer() {
+        assert_eq!(
+            footer_issues(&issue_footers(), "fixes: #3, #4\nFixes #3"),
+            ["3", "4"]
+        );
+    }
+
+    #[test]
+    fn refs_footer() {
+        assert_eq!(
+            footer_issues(&issue_footers(), "Refs #34\nCloses #12"),
+            ["34", "12"]
+        );
+    }
+
+    #[test]
+    fn footers_with_other_keywords_are_ignored() {
+        assert!(footer_issues(&issue_footers(), "Related #5\nSee #6 and #7").is_empty());
+        assert_eq!(
+            footer_issues(&[String::from("Related")], "Related #5\nCloses #12"),
+            ["5"]
+        );
+    }
+}
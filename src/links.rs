@@ -0,0 +1,107 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+use crate::config::{render_template, Config};
+
+// Every link in the changelog points at the shipper repository on GitHub,
+// unless the config names another repository or provides URL templates
+const DEFAULT_REPOSITORY: &str = "shipperstack/shipper";
+
+/// What the link line at the end of each release section points to
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    /// The comparison between the previous release and this one
+    #[default]
+    Compare,
+    /// The source tree at this release's tag
+    Tree,
+    /// The GitHub release page for this release
+    Release,
+}
+
+fn repository_url(config: &Config) -> String {
+    format!(
+        "https://github.com/{}",
+        config.repository.as_deref().unwrap_or(DEFAULT_REPOSITORY)
+    )
+}
+
+/// The `owner/name` slug of a remote URL. Understands the HTTPS
+/// (`https://github.com/owner/name.git`), SSH (`ssh://git@github.com/owner/name`)
+/// and scp-like (`git@github.com:owner/name.git`) forms.
+pub fn remote_slug(url: &str) -> Option<String> {
+    let url = url.trim();
+
+    let path = if let Some((_, rest)) = url.split_once("://") {
+        rest.split_once('/')?.1
+    } else {
+        url.split_once(':')?.1
+    };
+
+    let slug = path.trim_matches('/').trim_end_matches(".git");
+    slug.contains('/').then(|| slug.to_string())
+}
+
+pub fn compare_url(config: &Config, from: &str, to: &str) -> String {
+    match &config.compare_url_template {
+        Some(template) => render_template(template, &[("from", from), ("to", to)]),
+        None => format!("{}/compare/{from}...{to}", repository_url(config)),
+    }
+}
+
+pub fn commit_url(config: &Config, hash: &str) -> String {
+    match &config.commit_url_template {
+        Some(template) => render_template(template, &[("hash", hash)]),
+        None => format!("{}/commit/{hash}", repository_url(config)),
+    }
+}
+
+pub fn issue_url(config: &Config, number: &str) -> String {
+    match &config.issue_url_template {
+        Some(template) => render_template(template, &[("number", number)]),
+        None => format!("{}/issues/{number}", repository_url(config)),
+    }
+}
+
+/// Turns `#123` references in the entry into links, if an issue URL template
+/// is configured
+pub fn link_issues(config: &Config, entry: &str) -> String {
+    if config.issue_url_template.is_none() {
+        return entry.to_string();
+    }
+
+    let pattern = Regex::new(r"(^|[\s(])#(\d+)\b").unwrap();
+    pattern
+        .replace_all(entry, |cap: &Captures| {
+            format!("{}[#{}]({})", &cap[1], &cap[2], issue_url(config, &cap[2]))
+        })
+        .to_string()
+}
+
+/// URL of the link line for a release section, pointing at the release tags
+pub fn version_url(config: &Config, version: &str, previous_version: &str) -> String {
+    let tag = config.tag_name(version);
+    match config.link_style {
+        LinkStyle::Compare => compare_url(config, &config.tag_name(previous_version), &tag),
+        LinkStyle::Tree => format!("{}/tree/{tag}", repository_url(config)),
+        LinkStyle::Release => format!("{}/releases/tag/{tag}", repository_url(config)),
+    }
+}
+
+/// The start of the Unreleased link line, which always compares against HEAD
+pub fn unreleased_link_prefix(config: &Config) -> String {
+    format!(
+        "[{}]: {}",
+        config.unreleased_anchor(),
+        compare_url_prefix(config)
+    )
+}
+
+/// The part of every compare URL before the versions
+pub fn compare_url_prefix(config: &Config) -> String {
+    match &config.compare_url_template {
+        Some(template) => template[..template.find('{').unwrap_or(template.len())].to_string(),
+        None => format!("{}/compare/", repository_url(config)),
+    }
+}
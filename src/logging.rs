@@ -0,0 +1,85 @@
+use std::io;
+use std::process::{Command, ExitStatus, Output};
+use std::time::Instant;
+
+use tracing::{debug, debug_span, error, Level};
+
+/// Sends log events to stderr. Only errors are shown unless `verbose` is set.
+pub fn init(verbose: bool) {
+    let max_level = if verbose { Level::DEBUG } else { Level::ERROR };
+
+    tracing_subscriber::fmt()
+        .with_max_level(max_level)
+        .with_target(false)
+        .with_writer(io::stderr)
+        .init();
+}
+
+/// Runs the git command like [`Command::output`], logging its duration and exit status
+pub fn traced_output(command: &mut Command) -> io::Result<Output> {
+    traced(command, false, |command| {
+        let output = command.output()?;
+        Ok((output.status, output))
+    })
+}
+
+/// Like [`traced_output`], for commands that answer a question with their exit
+/// status, like `git rev-parse --verify`. A non-zero exit is only logged when
+/// verbose, since it's an answer rather than a failure.
+pub fn traced_query(command: &mut Command) -> io::Result<Output> {
+    traced(command, true, |command| {
+        let output = command.output()?;
+        Ok((output.status, output))
+    })
+}
+
+/// Runs the git command like [`Command::status`], logging its duration and exit status
+pub fn traced_status(command: &mut Command) -> io::Result<ExitStatus> {
+    traced(command, false, |command| {
+        let status = command.status()?;
+        Ok((status, status))
+    })
+}
+
+fn traced<T>(
+    command: &mut Command,
+    is_query: bool,
+    run: impl FnOnce(&mut Command) -> io::Result<(ExitStatus, T)>,
+) -> io::Result<T> {
+    let description = describe(command);
+    let span = debug_span!("git", command = %description);
+    let _entered = span.enter();
+
+    debug!("running");
+    let start = Instant::now();
+    let result = run(command);
+    let duration_ms = start.elapsed().as_millis();
+
+    match &result {
+        Ok((status, _)) if status.success() || is_query => {
+            debug!(duration_ms, %status, "finished")
+        }
+        // The span is only shown when verbose, so errors name the command themselves
+        Ok((status, _)) => error!(command = %description, duration_ms, %status, "failed"),
+        Err(e) => error!(command = %description, duration_ms, error = %e, "failed to start"),
+    }
+
+    result.map(|(_, value)| value)
+}
+
+/// The command line as it would be typed, e.g. `git push origin --tags`.
+/// Arguments with whitespace, like commit messages, are quoted.
+fn describe(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.contains(char::is_whitespace) {
+                format!("{arg:?}")
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,14 +1,49 @@
 use chrono::prelude::Local;
-use clap::{Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::env;
+use std::fmt;
 use std::fs;
-use std::io::BufReader;
-use std::process::Command;
-use std::{io::BufRead, path::Path};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 
-use semver::Version;
+use semver::{BuildMetadata, Version};
 
 use regex::Regex;
 
+use bump::{bump, detect_bump, BumpKind, NotesSince};
+use changelog::{
+    find_section, find_setext_heading, link_insertion_index, merge_entries,
+    normalize_section_spacing, parse_sections, section_body_end, Section,
+};
+use config::{render_template, Config, BULLET_MARKERS, CONFIG_FILE_NAME};
+use error::Error;
+use git::{query_git, run_git};
+use grouping::{
+    breaking_changes, entry_text, footer_issues, group_commits, parse_conventional, GroupBy,
+    SortOrder,
+};
+use links::{
+    commit_url, compare_url, compare_url_prefix, link_issues, remote_slug, unreleased_link_prefix,
+    version_url,
+};
+use logging::{traced_output, traced_status};
+use tracing::debug;
+use ui::{confirm, page, print_diff};
+
+mod bump;
+mod changelog;
+mod config;
+mod error;
+mod git;
+mod grouping;
+mod links;
+mod logging;
+mod ui;
+mod update;
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 // These filenames are unlikely to ever change
@@ -21,69 +56,591 @@ const VERSION_FILE_NAME: &str = "version.txt";
 #[command(version = VERSION)]
 #[command(about="Release orchestrator and changelog management program for shipper", long_about = None)]
 struct Cli {
-    #[arg(short, long)]
+    /// Print the version in version.txt and exit
+    #[arg(long)]
+    project_version: bool,
+    /// Log every git command with its duration and exit status
+    #[arg(short, long, global = true)]
     verbose: bool,
+    /// Run as if shipper-release was started in this directory
+    #[arg(short = 'C', long, global = true)]
+    directory: Option<PathBuf>,
+    /// Skip the check for a newer shipper-release, even if enabled in the config
+    #[arg(long, global = true)]
+    no_update_check: bool,
+    /// The `owner/name` of the GitHub repository that links point at
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_REPO")]
+    repository: Option<String>,
+    /// Fail instead of warning when the repository doesn't match the origin remote
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Template for compare links, with `{from}` and `{to}` placeholders
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_COMPARE_URL_TEMPLATE")]
+    compare_url_template: Option<String>,
+    /// Template for commit links, with a `{hash}` placeholder
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_COMMIT_URL_TEMPLATE")]
+    commit_url_template: Option<String>,
+    /// Template for issue links, with a `{number}` placeholder
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_ISSUE_URL_TEMPLATE")]
+    issue_url_template: Option<String>,
+    /// Prefix of the release tags, e.g. `v` for tags like `v1.2.3`
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_TAG_PREFIX")]
+    tag_prefix: Option<String>,
+    /// Use the prefix of the existing release tags, like `v` for `v1.2.3`,
+    /// instead of the configured one
+    #[arg(long, global = true, conflicts_with = "tag_prefix")]
+    tag_prefix_detect: bool,
+    /// Path of the git executable to run. Defaults to `git` from the `PATH`.
+    #[arg(long, global = true, env = "SHIPPER_RELEASE_GIT")]
+    git_path: Option<String>,
+    /// Skip the check that version.txt holds a valid semantic version before
+    /// anything else is done
+    #[arg(long, global = true)]
+    no_strict_semver: bool,
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Generates a CHANGELOG entry with the git commit log
-    Generate {
+    Generate(GenerateArgs),
+    /// Creates and pushes a new release to GitHub
+    Push(PushArgs),
+    /// Runs `generate` and `push` in one go, with a chance to review the changelog in between
+    Release(ReleaseArgs),
+    /// Updates the date of the topmost release section to today
+    AmendDate,
+    /// Prints the commit range the next release covers, e.g. `1.2.3...HEAD`
+    Range(RangeArgs),
+    /// Prints the combined changelog sections of every release after one version up to another
+    Diff {
+        /// The older version, whose own section is excluded
+        from: String,
+        /// The newer version, whose section is included
+        to: String,
+        /// Build fresh notes from the git log between the two tags instead
         #[arg(long)]
-        major: bool,
+        from_git: bool,
+        #[command(flatten)]
+        entry: EntryArgs,
+    },
+    /// Prints a roff man page for shipper-release to stdout
+    Man,
+    /// Inserts an empty section with a TBD date for a planned future version
+    Plan {
+        /// The planned version, which must be newer than the current one
+        version: String,
+    },
+    /// Renders notes for the commits between two refs, without touching the changelog or version.txt
+    Notes(NotesArgs),
+    /// Rebuilds the changelog section of an already tagged release from git
+    Regenerate {
+        /// The released version to rebuild
+        version: String,
+        /// Leave out commits that an older release section already lists
         #[arg(long)]
-        minor: bool,
-        #[arg(short, long)]
-        patch: bool,
+        dedup_against_history: bool,
+        #[command(flatten)]
+        entry: EntryArgs,
+    },
+    /// Recomputes every compare link from the version headings, so each compares
+    /// a release with the one below it and the Unreleased link starts at the latest release
+    FixLinks,
+    /// Lists the versions in the changelog with their release dates, newest first
+    List {
+        /// Format of the list
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+    /// Compares the entries of a release section with the commits of its range,
+    /// listing entries without a commit and commits without an entry
+    #[command(visible_alias = "verify-changelog")]
+    Audit {
+        /// The release to audit. Defaults to the newest release in the changelog.
+        version: Option<String>,
     },
-    /// Creates and pushes a new release to GitHub
-    Push,
 }
 
-fn main() {
-    if !check_running_directory() {
-        println!(
-            "Unable to find repository files. Are you sure you're running \
-this program in the shipper repository?"
-        );
-        return;
+#[derive(Args, Clone, Debug)]
+#[command(group(
+    ArgGroup::new("version_bump")
+        .args(["bump", "major", "minor", "patch", "rc"])
+        .multiple(false)
+))]
+struct GenerateArgs {
+    /// Part of the version to bump. Can be left out when only attaching `--build` metadata.
+    #[arg(long, value_enum, required_unless_present_any = ["major", "minor", "patch", "rc", "build"])]
+    bump: Option<BumpArg>,
+    /// Same as `--bump major`
+    #[arg(long, hide = true)]
+    major: bool,
+    /// Same as `--bump minor`
+    #[arg(long, hide = true)]
+    minor: bool,
+    /// Same as `--bump patch`
+    #[arg(short, long, hide = true)]
+    patch: bool,
+    /// Same as `--bump rc`
+    #[arg(long, hide = true)]
+    rc: bool,
+    /// Print the proposed changelog and version instead of writing them
+    #[arg(long)]
+    dry_run: bool,
+    /// Write the proposed files into this directory instead of printing them
+    #[arg(long, requires = "dry_run")]
+    output_dir: Option<PathBuf>,
+    /// Print the proposed files directly instead of through the pager
+    #[arg(long)]
+    no_pager: bool,
+    /// Write the changes without asking for confirmation
+    #[arg(short, long)]
+    yes: bool,
+    /// Write the changes without showing the diff for confirmation, as `release`
+    /// does since its push confirmation replaces it. Other checks still ask
+    /// unless `yes` is set.
+    #[arg(skip)]
+    skip_diff: bool,
+    /// Date for the release heading in YYYY-MM-DD format. Defaults to today.
+    #[arg(long, value_parser = parse_iso8601_date)]
+    date: Option<String>,
+    /// Attach semver build metadata, e.g. `ci.42`, to the new version. Without
+    /// a version flag the version numbers stay the same.
+    #[arg(long, value_parser = parse_build_metadata)]
+    build: Option<BuildMetadata>,
+    /// Cover every commit since this released version instead of only those
+    /// since the current one, e.g. to consolidate several skipped releases
+    #[arg(long, value_name = "VERSION")]
+    since_version: Option<String>,
+    /// Add this text as an entry of the new section, for changes without a
+    /// commit. Can be repeated.
+    #[arg(long, value_name = "TEXT", allow_hyphen_values = true)]
+    append_entry: Vec<String>,
+    /// Print how many entries each section of the new release has
+    #[arg(long)]
+    stat: bool,
+    /// Leave out commits that a release section in the changelog already lists
+    #[arg(long)]
+    dedup_against_history: bool,
+    /// Record the range, its resolved commits and the hashes of the listed
+    /// commits in this JSON file, e.g. `.release-range`, for later audits, or
+    /// print it with `-`
+    #[arg(long, value_name = "PATH")]
+    commit_range_file: Option<PathBuf>,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    entry: EntryArgs,
+}
+
+#[derive(Args, Debug)]
+struct ReleaseArgs {
+    /// Open the changelog in git's editor to review the new section before pushing
+    #[arg(long)]
+    edit: bool,
+    #[command(flatten)]
+    generate: GenerateArgs,
+    #[command(flatten)]
+    push: PushArgs,
+}
+
+/// Options controlling which commits are included in the changelog
+#[derive(Args, Clone, Debug, Default)]
+struct FilterArgs {
+    /// Leave out commits whose author name or email matches this exact value or
+    /// regex, e.g. `dependabot[bot]`. Can be repeated.
+    #[arg(long)]
+    exclude_author: Vec<String>,
+    /// Only include commits whose author matches this exact value or regex.
+    /// Can be repeated.
+    #[arg(long)]
+    only_author: Vec<String>,
+}
+
+/// Options controlling how each commit is rendered as a changelog entry
+#[derive(Args, Clone, Debug, Default)]
+struct EntryArgs {
+    /// Include commit bodies as indented paragraphs beneath each entry
+    #[arg(long)]
+    include_body: bool,
+    /// Append a link to the commit after each entry
+    #[arg(long)]
+    show_hash: bool,
+    /// Strip leading ticket keys like `PROJ-123: ` from each entry, linking them
+    /// at the end of the entry if a ticket URL is configured
+    #[arg(long)]
+    strip_ticket_prefix: bool,
+    /// Truncate each entry to this many characters, keeping a trailing `(#123)` reference
+    #[arg(long, value_name = "N")]
+    max_subject_length: Option<usize>,
+    /// Append the issues referenced by footers like `Closes #12` to each entry
+    #[arg(long)]
+    footer_issues: bool,
+    /// List the newest commits first instead of the oldest
+    #[arg(long, visible_alias = "reverse-entries")]
+    newest_first: bool,
+    /// Order of the entries within each section. Defaults to the configured order.
+    #[arg(long, value_enum)]
+    sort: Option<SortOrder>,
+    /// List each bullet in the body of a squash merge like `Title (#123)` as its
+    /// own entry, instead of only the title
+    #[arg(long)]
+    expand_squash: bool,
+    /// Join a subject that wraps onto the following lines, before the first
+    /// blank line, into one entry instead of using only its first line
+    #[arg(long)]
+    join_wrapped_subjects: bool,
+}
+
+impl GenerateArgs {
+    /// The bump selected by `--bump` or one of the older flags. Clap ensures
+    /// at most one is given.
+    fn bump_arg(&self) -> Option<BumpArg> {
+        let flags = [
+            (self.major, BumpArg::Major),
+            (self.minor, BumpArg::Minor),
+            (self.patch, BumpArg::Patch),
+            (self.rc, BumpArg::Rc),
+        ];
+
+        self.bump.or_else(|| {
+            flags
+                .iter()
+                .find(|(is_set, _)| *is_set)
+                .map(|(_, bump_arg)| *bump_arg)
+        })
+    }
+}
+
+/// The bumps `--bump` accepts
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum BumpArg {
+    Major,
+    Minor,
+    Patch,
+    /// The next `-rc.N` release candidate
+    Rc,
+    /// Detected from the conventional commits since the last release: major
+    /// for breaking changes, minor for features and patch for fixes
+    Auto,
+}
+
+impl BumpArg {
+    /// The bump kind, or `None` for `auto`, which depends on the commits
+    fn kind(self) -> Option<BumpKind> {
+        match self {
+            BumpArg::Major => Some(BumpKind::Major),
+            BumpArg::Minor => Some(BumpKind::Minor),
+            BumpArg::Patch => Some(BumpKind::Patch),
+            BumpArg::Rc => Some(BumpKind::Rc),
+            BumpArg::Auto => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct PushArgs {
+    /// Only create and push the tag, for when the release commit already exists
+    #[arg(long)]
+    tag_only: bool,
+    /// Sign the release commit and tag with GPG
+    #[arg(short, long)]
+    sign: bool,
+    /// Remote to push the release to. Can be repeated, or given as a comma
+    /// separated list, to push to several remotes.
+    #[arg(long, env = "SHIPPER_RELEASE_REMOTE", value_delimiter = ',')]
+    remote: Vec<String>,
+    /// If pushing fails before any remote received the release, delete the tag
+    /// and undo the commit this run created
+    #[arg(long)]
+    rollback_on_failure: bool,
+    /// Print the release commit message and exit without running any git commands
+    #[arg(long)]
+    print_commit_message: bool,
+    /// Verify the signed tag with `git tag -v` before pushing
+    #[arg(long)]
+    verify_signature: bool,
+    /// Shell command, e.g. a build, that must succeed after the release commit
+    /// is created and before it is tagged. Overrides the configured one.
+    #[arg(long, value_name = "COMMAND")]
+    verify: Option<String>,
+    /// Skip the pre-commit, commit-msg and pre-push hooks, like git's own `--no-verify`
+    #[arg(long)]
+    no_verify: bool,
+    /// Only run the checks done before releasing and report each, without
+    /// committing, tagging or pushing anything
+    #[arg(long)]
+    check: bool,
+    /// Create an annotated tag with the contents of this file as its message,
+    /// instead of the tag_message template
+    #[arg(long, value_name = "PATH")]
+    tag_message_file: Option<PathBuf>,
+    /// Stage every change in the working tree for the release commit, not
+    /// only the files `generate` writes
+    #[arg(long, conflicts_with = "tag_only")]
+    commit_all: bool,
+    /// Make and push the release commit without tagging it, for when CI
+    /// creates the tag
+    #[arg(long, conflicts_with_all = ["tag_only", "verify_signature", "tag_message_file"])]
+    no_tag: bool,
+    /// Author of the release commit, as `Name <email>`, e.g. for a release bot
+    #[arg(long, value_name = "IDENTITY", value_parser = parse_identity)]
+    author: Option<Identity>,
+    /// Committer of the release commit and tagger of the tag, as `Name <email>`.
+    /// The git config is left unchanged.
+    #[arg(long, value_name = "IDENTITY", value_parser = parse_identity)]
+    committer: Option<Identity>,
+    /// Open the release commit message in git's editor before committing
+    #[arg(long, conflicts_with = "tag_only")]
+    edit_message: bool,
+}
+
+/// A git identity like `Name <email>`
+#[derive(Clone, Debug)]
+struct Identity {
+    name: String,
+    email: String,
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
     }
+}
+
+/// Output format of the `notes` subcommand
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum NotesFormat {
+    /// The same Markdown that goes into the changelog
+    Markdown,
+    /// Text without any Markdown syntax, e.g. for emails
+    Plain,
+    /// Structured sections and entries for other tools
+    Json,
+}
 
+/// Output format of the `list` subcommand
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ListFormat {
+    /// One version per line with its date
+    Table,
+    /// An array of versions and dates for other tools
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct NotesArgs {
+    /// The older ref, whose commits are excluded
+    #[arg(long)]
+    from: String,
+    /// The newer ref, whose commits are included
+    #[arg(long)]
+    to: String,
+    /// Write the notes to this file instead of printing them, or print them with `-`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Format of the notes
+    #[arg(long, value_enum, default_value_t = NotesFormat::Markdown)]
+    format: NotesFormat,
+    /// Wrap the Markdown notes in a `## What's Changed` heading and a
+    /// `**Full Changelog**` compare link, like GitHub's generated release notes
+    #[arg(long)]
+    github_style: bool,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    entry: EntryArgs,
+}
+
+#[derive(Args, Debug, Default)]
+struct RangeArgs {
+    /// Start of the range. Defaults to the tag of the version in version.txt.
+    #[arg(long, conflicts_with = "since_tag")]
+    from: Option<String>,
+    /// End of the range. Defaults to HEAD.
+    #[arg(long)]
+    to: Option<String>,
+    /// Start the range at the most recent tag reachable from the end of the range
+    #[arg(long)]
+    since_tag: bool,
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    match &cli.command {
-        Commands::Generate {
-            major,
-            minor,
-            patch,
-        } => {
-            if !major && !minor && !patch {
-                println!(
-                    "At least one version flag should be specified. Valid \
-options are: --major, --minor, --patch"
-                );
-                return;
-            }
-            if (*major || *minor) && *patch || (*major && *minor) {
-                println!("Only one version flag should be specified.");
-                return;
-            }
-            generate_changelog(*major, *minor, *patch);
+    // Clap can't require either a subcommand or a flag, so this is checked here
+    match (&cli.command, cli.project_version) {
+        (Some(_), true) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--project-version can't be used with a subcommand",
+            )
+            .exit(),
+        (None, false) => Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "a subcommand or --project-version is required",
+            )
+            .exit(),
+        _ => {}
+    }
+
+    logging::init(cli.verbose);
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // With stdout already closed, e.g. by `| head`, only the exit code is left
+            let _ = writeln!(io::stdout(), "{e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Error> {
+    // The man page doesn't depend on the repository, so it can be generated anywhere
+    if let Some(Commands::Man) = cli.command {
+        clap_mangen::Man::new(Cli::command())
+            .render(&mut io::stdout())
+            .map_err(|e| Error::Other(format!("Unable to write the man page: {e}")))?;
+        return Ok(());
+    }
+
+    if let Some(directory) = &cli.directory {
+        if let Err(e) = env::set_current_dir(directory) {
+            return Err(Error::Other(format!(
+                "Unable to change to directory {}: {e}",
+                directory.display()
+            )));
+        }
+    }
+
+    // Git is needed for the repository check, so its path is known beforehand.
+    // A missing config file just gives the defaults outside the repository.
+    let mut config = Config::load()?;
+    if let Some(git_path) = cli.git_path.clone().or_else(|| config.git_path.clone()) {
+        git::set_program(git_path);
+        git::check_program()?;
+    }
+
+    // Shown with --verbose, to tell where the files were looked for
+    let working_directory = env::current_dir().unwrap_or_default();
+    debug!(changelog = %working_directory.join(CHANGELOG_FILE_NAME).display(), "resolved path");
+    debug!(version_file = %working_directory.join(VERSION_FILE_NAME).display(), "resolved path");
+
+    // Notes are rendered from git alone, so they work in any repository
+    let is_headless = matches!(cli.command, Some(Commands::Notes(_)));
+    if !check_running_directory(is_headless) {
+        return Err(Error::NotInRepository(format!(
+            "Unable to find repository files in {}. Are you sure you're running \
+this program in the shipper repository? Run with --verbose to see the paths that were checked.",
+            working_directory.display()
+        )));
+    }
+
+    // Setext headings would silently not be found as sections
+    if !is_headless {
+        check_heading_style()?;
+    }
+
+    // A version like `1.2` would otherwise only fail once git work has started
+    if !is_headless && !cli.no_strict_semver {
+        let version = get_last_version()?;
+        if let Err(e) = Version::parse(&version) {
+            return Err(Error::Other(format!(
+                "{VERSION_FILE_NAME} contains {version:?}, which is not a valid semantic version \
+like 1.2.3: {e}"
+            )));
+        }
+    }
+
+    // Without a subcommand, --project-version was passed
+    let Some(command) = &cli.command else {
+        println!("{}", get_last_version()?);
+        return Ok(());
+    };
+
+    // Settings from the command line or the environment override the config file
+    if cli.repository.is_some() {
+        config.repository = cli.repository.clone();
+    }
+    if cli.compare_url_template.is_some() {
+        config.compare_url_template = cli.compare_url_template.clone();
+    }
+    if cli.commit_url_template.is_some() {
+        config.commit_url_template = cli.commit_url_template.clone();
+    }
+    if cli.issue_url_template.is_some() {
+        config.issue_url_template = cli.issue_url_template.clone();
+    }
+    if let Some(tag_prefix) = &cli.tag_prefix {
+        config.tag_prefix = tag_prefix.clone();
+    }
+    if cli.tag_prefix_detect {
+        config.tag_prefix = detect_tag_prefix()?;
+    }
+    config.validate()?;
+    check_repository_matches_origin(&config, cli.strict)?;
+
+    // Check for a newer shipper-release while the command runs
+    let update_check = (config.update_check && !cli.no_update_check).then(update::spawn_check);
+
+    let result = run_command(command, &config);
+
+    if let Some(update_check) = &update_check {
+        update::print_notice(update_check);
+    }
+
+    result
+}
+
+fn run_command(command: &Commands, config: &Config) -> Result<(), Error> {
+    match command {
+        Commands::Generate(args) => {
+            generate_changelog(config, args, args.bump_arg())?;
+            Ok(())
         }
-        Commands::Push => {
-            push();
+        Commands::Push(args) => push(config, args),
+        Commands::Release(args) => release(config, args),
+        Commands::AmendDate => amend_date(config),
+        Commands::Range(args) => {
+            let (from, to) = compute_range(config, args)?;
+            println!("{from}...{to}");
+            Ok(())
         }
+        Commands::Diff {
+            from,
+            to,
+            from_git,
+            entry,
+        } => diff(config, entry, from, to, *from_git),
+        Commands::Notes(args) => notes(config, args),
+        Commands::Plan { version } => plan(config, version),
+        Commands::Man => unreachable!("The man page is rendered before the repository checks"),
+        Commands::Regenerate {
+            version,
+            dedup_against_history,
+            entry,
+        } => regenerate(config, entry, version, *dedup_against_history),
+        Commands::List { format } => list(config, *format),
+        Commands::FixLinks => fix_links(config),
+        Commands::Audit { version } => audit(config, version.as_deref()),
     }
 }
 
-/// Function to check if shipper-release is running in the correct directory
-fn check_running_directory() -> bool {
-    if !Path::new(".git").is_dir() {
+/// Function to check if shipper-release is running in the correct directory.
+/// Headless commands only need a git repository.
+fn check_running_directory(is_headless: bool) -> bool {
+    // In a worktree `.git` is a file pointing at the main repository, so let
+    // git decide whether it's valid
+    if !Path::new(".git").exists() || !is_git_repository() {
         return false;
     }
 
+    if is_headless {
+        return true;
+    }
+
     if !Path::new(CHANGELOG_FILE_NAME).exists() {
         return false;
     }
@@ -95,195 +652,3651 @@ fn check_running_directory() -> bool {
     true
 }
 
+fn check_heading_style() -> Result<(), Error> {
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.lines().collect();
+
+    match find_setext_heading(&lines) {
+        Some(index) => Err(Error::Other(format!(
+            "Line {} of {CHANGELOG_FILE_NAME} is an underlined heading: {}\n\
+Only headings like `# [1.2.3] - 2023-01-01` are understood. Rewrite the underlined version \
+headings in that style.",
+            index + 1,
+            lines[index].trim_end()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Warns, or fails when strict, if the configured repository isn't the one
+/// the origin remote points at, since the links would then go elsewhere
+fn check_repository_matches_origin(config: &Config, strict: bool) -> Result<(), Error> {
+    let Some(repository) = &config.repository else {
+        return Ok(());
+    };
+
+    let output = query_git(
+        git::command().arg("remote").arg("get-url").arg("origin"),
+        "Failed to look up the origin remote!",
+    )?;
+    if !output.status.success() {
+        return Ok(());
+    }
+
+    let origin = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match remote_slug(&origin) {
+        Some(slug) if !slug.eq_ignore_ascii_case(repository) => {
+            let message = format!(
+                "The repository {repository} doesn't match the origin remote {origin}, so the \
+links won't point at it!"
+            );
+            if strict {
+                return Err(Error::Other(message));
+            }
+            println!("Warning: {message}");
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn is_git_repository() -> bool {
+    query_git(
+        git::command().arg("rev-parse").arg("--git-dir"),
+        "Failed to find the git repository!",
+    )
+    .is_ok_and(|output| output.status.success())
+}
+
+fn parse_build_metadata(build: &str) -> Result<BuildMetadata, String> {
+    BuildMetadata::new(build).map_err(|e| format!("{build} is not valid build metadata: {e}"))
+}
+
+fn parse_identity(identity: &str) -> Result<Identity, String> {
+    let pattern = Regex::new(r"^([^<>]*[^<>\s])\s*<([^<>\s]+@[^<>\s]+)>$").unwrap();
+
+    let cap = pattern.captures(identity.trim()).ok_or_else(|| {
+        format!("{identity} is not a valid identity! Use the form `Name <email@example.com>`.")
+    })?;
+
+    Ok(Identity {
+        name: cap[1].to_string(),
+        email: cap[2].to_string(),
+    })
+}
+
+fn parse_iso8601_date(date: &str) -> Result<String, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .map_err(|e| format!("{date} is not a valid YYYY-MM-DD date: {e}"))
+}
+
 fn today_iso8601() -> String {
     let today = Local::now();
 
     today.format("%Y-%m-%d").to_string()
 }
 
-fn generate_changelog(major: bool, minor: bool, patch: bool) {
+/// Writes the new release into the changelog and version file. Returns whether
+/// they were written, rather than only previewed or aborted.
+fn generate_changelog(
+    config: &Config,
+    args: &GenerateArgs,
+    bump_arg: Option<BumpArg>,
+) -> Result<bool, Error> {
     // Get last version
-    let last_version = get_last_version();
+    let last_version = get_last_version()?;
+
+    let Ok(parsed_last_version) = Version::parse(&last_version) else {
+        return Err(Error::Other(format!(
+            "The version {last_version} in {VERSION_FILE_NAME} is not a valid semantic version!"
+        )));
+    };
+
+    // Build metadata belongs to a single build, so it never carries over
+    let bump_kind = match bump_arg {
+        Some(BumpArg::Auto) => Some(detect_release_bump(config, args, &last_version)?),
+        bump_arg => bump_arg.and_then(BumpArg::kind),
+    };
+    let mut new_version = match bump_kind {
+        Some(bump_kind) => bump(&parsed_last_version, bump_kind),
+        None => Version {
+            build: BuildMetadata::EMPTY,
+            ..parsed_last_version.clone()
+        },
+    };
+    if let Some(build) = &args.build {
+        new_version.build = build.clone();
+    }
+    let is_finalizing = !parsed_last_version.pre.is_empty() && new_version.pre.is_empty();
+    let new_version = new_version.to_string();
+
+    // Safety net in case the version bump logic ever produces an invalid string
+    check_new_version(&new_version)?;
+
+    // The new section covers everything since this version
+    let previous_version = match &args.since_version {
+        Some(since_version) => {
+            if Version::parse(since_version).is_err() {
+                return Err(Error::Usage(format!(
+                    "{since_version} is not a valid semantic version!"
+                )));
+            }
+            let since_tag = config.tag_name(since_version);
+            if !tag_exists(&since_tag)? {
+                return Err(Error::Other(format!(
+                    "The tag {since_tag} doesn't exist! --since-version must be a released version."
+                )));
+            }
+            since_version.clone()
+        }
+        // The prereleases' commits are already listed in their own sections,
+        // unless the final release is configured to list them again
+        None if is_finalizing && config.finalize_notes_since == NotesSince::Release => {
+            match get_previous_version(config, &last_version, false) {
+                Some(previous_release) => previous_release,
+                None => {
+                    println!(
+                        "Warning: no release before {last_version} is tagged, so only the \
+commits since {last_version} are listed."
+                    );
+                    last_version.clone()
+                }
+            }
+        }
+        None => last_version.clone(),
+    };
+
+    let (from, to) = compute_range(
+        config,
+        &RangeArgs {
+            from: Some(config.tag_name(&previous_version)),
+            ..RangeArgs::default()
+        },
+    )?;
 
-    let git_log_raw = get_git_log_raw(&last_version);
+    // Running `generate` again right after a release is the most common mistake
+    if !has_commits_since(&from, &to)? && args.append_entry.is_empty() {
+        return Err(Error::NothingToRelease(format!(
+            "No commits since {previous_version}; nothing to release."
+        )));
+    }
+    let all_commits = read_commits(&from, &to)?;
 
-    let new_version = get_new_version(&last_version, major, minor, patch);
+    if config.check_remote_tag {
+        check_remote_tag(config, &from);
+    }
 
     println!("New version is {}", new_version);
 
-    let binding = fs::read_to_string(CHANGELOG_FILE_NAME)
-        .expect("Failed to read the changelog file into memory!");
-    let old_changelog = binding.split('\n');
+    let binding = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let old_changelog: Vec<&str> = binding.split('\n').collect();
+
+    // A base that isn't the current version means a release wasn't recorded,
+    // and the new section's links would carry the mistake along
+    let unreleased_link_prefix = unreleased_link_prefix(config);
+    let expected_unreleased_link = format!(
+        "[{}]: {}",
+        config.unreleased_anchor(),
+        compare_url(config, &config.tag_name(&last_version), "HEAD")
+    );
+    if let Some(unreleased_link) = old_changelog
+        .iter()
+        .find(|line| line.starts_with(&unreleased_link_prefix))
+    {
+        if unreleased_link.trim_end() != expected_unreleased_link {
+            println!(
+                "Warning: the Unreleased link doesn't compare against {last_version}, so the \
+changelog's Unreleased base is out of sync. A previous release may not have been recorded \
+properly; `fix-links` recomputes the links."
+            );
+        }
+    }
+
+    // Spanning several releases with --since-version includes their release commits
+    let mut commits = filter_commits(
+        &args.filter,
+        all_commits
+            .iter()
+            .map(OwnedCommit::as_commit)
+            .filter(|commit| !is_release_commit(config, commit)),
+    );
+    if commits.is_empty() && !all_commits.is_empty() && args.append_entry.is_empty() {
+        println!(
+            "Warning: all {} commits were filtered out, so the new section will be empty!",
+            all_commits.len()
+        );
+    }
 
-    let mut new_changelog: Vec<String> = Vec::new();
+    if args.dedup_against_history {
+        commits = dedup_against_history(config, &old_changelog, &new_version, commits);
+    }
+    warn_unknown_scopes(config, &commits);
 
-    let today_iso8601 = today_iso8601();
+    // Manual entries are grouped and sorted like commits, but have no hash to
+    // link. Any bullet marker they were given with is replaced by the configured one.
+    commits.extend(args.append_entry.iter().map(|entry| {
+        let entry = entry.trim();
+        Commit {
+            hash: "",
+            author: "",
+            msg: BULLET_MARKERS
+                .iter()
+                .find_map(|marker| entry.strip_prefix(&format!("{marker} ")))
+                .unwrap_or(entry),
+            wrapped: "",
+            body: "",
+        }
+    }));
 
-    // Loop until unreleased link line
-    for line in old_changelog {
-        if line.starts_with("[Unreleased]: https://github.com/shipperstack/shipper/compare/") {
-            new_changelog.push(format!("[Unreleased]: https://github.com/shipperstack/shipper/compare/{new_version}...HEAD"));
+    if args.stat {
+        println!("{}", section_stats(config, &args.entry, commits.clone()));
+    }
+
+    let release_date = args.date.clone().unwrap_or_else(today_iso8601);
 
-            // Push two empty lines for readability
-            new_changelog.push(String::from(""));
-            new_changelog.push(String::from(""));
+    // Resolved before anything is written, so a bad range writes nothing
+    let commit_range = match &args.commit_range_file {
+        Some(path) => Some((path, commit_range_record(&from, &to, &commits)?)),
+        None => None,
+    };
 
-            // Create new changelog entry
-            new_changelog.push(format!("# [{new_version}] - {today_iso8601}"));
+    let (new_section, new_link) = render_section(
+        config,
+        &args.entry,
+        commits,
+        &new_version,
+        &previous_version,
+        &release_date,
+    );
 
-            new_changelog.push(String::from(""));
+    let sections = parse_sections(&old_changelog);
+    let new_changelog = match find_section(&sections, &new_version) {
+        // Re-running on the same day adds the missing entries to the section
+        Some(section) if section.date.as_deref() == Some(release_date.as_str()) => {
+            // The entries are merged from the new section's body, below its heading
+            let (merged, added) =
+                merge_entries(&old_changelog, section, &new_section[1..], config.bullet);
+            println!(
+                "The section for {new_version} from {release_date} already exists, merging \
+{added} new entries into it."
+            );
+            merged
+        }
+        // A section planned with `plan` gets its entries and date with the release
+        Some(section) if is_planned(section) => {
+            let (mut merged, added) =
+                merge_entries(&old_changelog, section, &new_section[1..], config.bullet);
+            merged[section.heading] = new_section[0].clone();
 
-            // Add all commit entries (to be sorted later)
-            for commit in parse_git_log(&git_log_raw) {
-                let commit_msg = commit.msg;
-                new_changelog.push(format!("- {commit_msg}"));
+            let planned_link_prefix = format!("[{new_version}]: ");
+            for line in merged.iter_mut() {
+                if line.starts_with(&unreleased_link_prefix) {
+                    *line = format!(
+                        "[{}]: {}",
+                        config.unreleased_anchor(),
+                        compare_url(config, &config.tag_name(&new_version), "HEAD")
+                    );
+                } else if line.starts_with(&planned_link_prefix) {
+                    *line = new_link.clone();
+                }
             }
+            println!(
+                "Filling in the section planned for {new_version} with {added} entries and the \
+date {release_date}."
+            );
+            merged
+        }
+        Some(_) => {
+            return Err(Error::Other(format!(
+                "The changelog already has a section for {new_version}!"
+            )));
+        }
+        None => insert_section(
+            config,
+            &old_changelog,
+            &new_version,
+            new_section,
+            new_link,
+            true,
+        )?,
+    };
 
-            new_changelog.push(String::from(""));
+    let current_version_file = read_file(Path::new(VERSION_FILE_NAME))?;
+    let release_file = match &config.release_file {
+        Some(template) => Some((
+            PathBuf::from(render_template(template, &[("version", &new_version)])),
+            release_file_contents(&new_changelog, &new_version)?,
+        )),
+        None => None,
+    };
 
-            new_changelog.push(format!("[{new_version}]: https://github.com/shipperstack/shipper/compare/{last_version}...{new_version}"));
-            continue;
-        } else {
-            new_changelog.push(line.to_string());
+    if args.dry_run {
+        match &args.output_dir {
+            Some(output_dir) => {
+                fs::create_dir_all(output_dir).map_err(|e| {
+                    Error::Other(format!(
+                        "Unable to create the output directory {}: {e}",
+                        output_dir.display()
+                    ))
+                })?;
+                write_file(
+                    &output_dir.join(CHANGELOG_FILE_NAME),
+                    &new_changelog.join("\n"),
+                )?;
+                write_file(
+                    &output_dir.join(VERSION_FILE_NAME),
+                    &version_file_contents(&current_version_file, &new_version),
+                )?;
+                if let Some((path, contents)) = &release_file {
+                    write_release_file(&output_dir.join(path), contents)?;
+                }
+
+                println!("Proposed files written to {}.", output_dir.display());
+            }
+            None => {
+                let mut preview = format!(
+                    "--- {CHANGELOG_FILE_NAME} ---\n{}\n--- {VERSION_FILE_NAME} ---\n{new_version}\n",
+                    new_changelog.join("\n")
+                );
+                if let Some((path, contents)) = &release_file {
+                    preview.push_str(&format!("--- {} ---\n{contents}", path.display()));
+                }
+                page(&preview, !args.no_pager);
+            }
+        }
+        return Ok(false);
+    }
+
+    // Manual edits would get mixed into the rewritten changelog
+    if changelog_has_uncommitted_changes()? {
+        println!("Warning: {CHANGELOG_FILE_NAME} has uncommitted changes, which will be kept in the rewritten changelog.");
+        if !args.yes && !confirm("Continue anyway?", false) {
+            println!("Aborted. No files were changed. Commit or stash the changes, or pass --yes to skip this check.");
+            return Ok(false);
+        }
+    }
+
+    if !args.yes && !args.skip_diff {
+        print_diff(&old_changelog, &new_changelog);
+        if !confirm("Write these changes?", true) {
+            println!("Aborted. No files were changed.");
+            return Ok(false);
         }
     }
 
     // Overwrite changelog file
-    fs::write(CHANGELOG_FILE_NAME, new_changelog.join("\n"))
-        .expect("Failed to write the new changelog contents!");
+    write_file(Path::new(CHANGELOG_FILE_NAME), &new_changelog.join("\n"))?;
 
     println!("Changelog entries added.");
 
-    fs::write(VERSION_FILE_NAME, new_version).expect("Failed to write the new version text file!");
+    write_file(
+        Path::new(VERSION_FILE_NAME),
+        &version_file_contents(&current_version_file, &new_version),
+    )?;
 
     println!("Version text updated.");
 
-    println!("Done! Modify the changelog items as necessary, add with `git add .`, and run `push`.")
+    if let Some((path, contents)) = &release_file {
+        write_release_file(path, contents)?;
+        println!("Release section written to {}.", path.display());
+    }
+
+    if let Some((path, record)) = &commit_range {
+        let record = format!("{}\n", serde_json::to_string_pretty(record).unwrap());
+        if write_output(path, &record)? {
+            println!("Commit range written to {}.", path.display());
+        }
+    }
+
+    println!(
+        "Done! Modify the changelog items as necessary and run `push`, which stages the release files."
+    );
+
+    Ok(true)
 }
 
-fn get_new_version(last_version_raw: &str, major: bool, minor: bool, patch: bool) -> String {
-    let mut last_version = Version::parse(last_version_raw).unwrap();
+/// Whether the section was planned with `plan`, so it has a placeholder like
+/// TBD instead of a release date
+fn is_planned(section: &Section) -> bool {
+    section
+        .date
+        .as_deref()
+        .is_some_and(|date| parse_iso8601_date(date).is_err())
+}
 
-    if major {
-        last_version.major += 1;
-        last_version.minor = 0;
-        last_version.patch = 0;
-    } else if minor {
-        last_version.minor += 1;
-        last_version.patch = 0;
-    } else if patch {
-        last_version.patch += 1;
-    } else {
-        panic!("This error shouldn't occur -- failed to get new version string!");
+/// Fails if the new version doesn't parse back as a semantic version, before
+/// anything is written
+fn check_new_version(new_version: &str) -> Result<(), Error> {
+    match Version::parse(new_version) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Other(format!(
+            "The new version {new_version} is not a valid semantic version: {e}"
+        ))),
+    }
+}
+
+/// The version's section of the changelog on its own, with its link line
+fn release_file_contents(changelog: &[String], version: &str) -> Result<String, Error> {
+    let sections = parse_sections(changelog);
+    let Some(section) = find_section(&sections, version) else {
+        return Err(Error::Other(format!(
+            "Unable to find the section for {version} in the new changelog!"
+        )));
+    };
+
+    let mut lines: Vec<&str> = changelog[section.heading..section_body_end(changelog, section)]
+        .iter()
+        .map(String::as_str)
+        .collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
     }
+    // The link line may be in a block at the bottom of the changelog
+    let link_prefix = format!("[{version}]: ");
+    if let Some(link) = changelog.iter().find(|line| line.starts_with(&link_prefix)) {
+        lines.push("");
+        lines.push(link);
+    }
+    Ok(format!("{}\n", lines.join("\n")))
+}
 
-    last_version.to_string()
+/// Reads the file, failing with its path instead of panicking, e.g. when it
+/// doesn't exist
+fn read_file(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path)
+        .map_err(|e| Error::Other(format!("Unable to read {}: {e}", path.display())))
 }
 
-fn get_git_log_raw(last_version: &str) -> String {
-    // Get git log between last version and HEAD
-    let git_log_output = Command::new("git")
-        .arg("log")
-        .arg("--oneline")
-        .arg("--reverse")
-        .arg(format!("{last_version}...HEAD"))
-        .output()
-        .unwrap();
+/// Writes the file, failing with its path instead of panicking, e.g. when its
+/// directory doesn't exist
+fn write_file(path: &Path, contents: &str) -> Result<(), Error> {
+    fs::write(path, contents)
+        .map_err(|e| Error::Other(format!("Unable to write {}: {e}", path.display())))
+}
 
-    if !git_log_output.status.success() {
-        panic!("Failed to execute git log command!");
+/// Writes an output file, or prints it with `-`, as usual on the command line.
+/// Returns whether it was written to a file.
+fn write_output(path: &Path, contents: &str) -> Result<bool, Error> {
+    if path == Path::new("-") {
+        print!("{contents}");
+        return Ok(false);
     }
 
-    String::from_utf8(git_log_output.stdout).unwrap()
+    write_file(path, contents)?;
+    Ok(true)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Commit<'a> {
-    msg: &'a str,
+/// Writes the release file, creating its directories as needed
+fn write_release_file(path: &Path, contents: &str) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            Error::Other(format!(
+                "Unable to create the release file's directory {}: {e}",
+                parent.display()
+            ))
+        })?;
+    }
+    write_file(path, contents)
 }
 
-fn parse_git_log(stdout: &str) -> impl Iterator<Item = Commit> + '_ {
-    let pattern = Regex::new(
-        r"(?x)
-            ([0-9a-fA-F]+) # commit hash
-            (.*)           # The commit message",
-    )
-    .unwrap();
+/// Inserts the section and its link line below the Unreleased section. For a
+/// release, the Unreleased link is updated to compare against the new version.
+fn insert_section(
+    config: &Config,
+    old_changelog: &[&str],
+    version: &str,
+    section: Vec<String>,
+    link: String,
+    is_release: bool,
+) -> Result<Vec<String>, Error> {
+    let sections = parse_sections(old_changelog);
 
-    stdout
-        .lines()
-        .filter_map(move |line| pattern.captures(line))
-        .map(|cap| Commit {
-            msg: cap.get(2).unwrap().as_str().trim(),
+    // Everything above the first section is a preamble, like the title and a
+    // note about the format, which is copied byte for byte
+    let preamble_end = sections
+        .first()
+        .map_or(old_changelog.len(), |section| section.heading);
+    let mut new_changelog: Vec<String> = old_changelog[..preamble_end]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+
+    let unreleased_link_prefix = unreleased_link_prefix(config);
+    // Without the link, the section would silently not be inserted
+    let Some(unreleased_link) = old_changelog
+        .iter()
+        .skip(preamble_end)
+        .position(|line| line.starts_with(&unreleased_link_prefix))
+        .map(|index| index + preamble_end)
+    else {
+        return Err(Error::Other(format!(
+            "Unable to find the Unreleased link in {CHANGELOG_FILE_NAME}! Searched for a line \
+starting with `{unreleased_link_prefix}`. Do the unreleased_heading and unreleased_anchor settings \
+match the changelog?"
+        )));
+    };
+
+    // Changelogs that keep every link definition in a block at the bottom have
+    // the Unreleased link after the end of the Unreleased section. The new
+    // section then goes before the next section, and its link into the block.
+    let bottom_links = match (
+        unreleased_link,
+        find_section(&sections, &config.unreleased_heading),
+    ) {
+        (link, Some(unreleased)) if link >= unreleased.end => Some((
+            unreleased.end,
+            link_insertion_index(old_changelog, link, version),
+        )),
+        _ => None,
+    };
+
+    for (index, line) in old_changelog.iter().enumerate().skip(preamble_end) {
+        if let Some((section_index, link_index)) = bottom_links {
+            if index == section_index {
+                new_changelog.extend(section.iter().cloned());
+            }
+            if index == link_index {
+                new_changelog.push(link.clone());
+            }
+        }
+
+        if index == unreleased_link {
+            if is_release {
+                new_changelog.push(format!(
+                    "[{}]: {}",
+                    config.unreleased_anchor(),
+                    compare_url(config, &config.tag_name(version), "HEAD")
+                ));
+            } else {
+                new_changelog.push(line.to_string());
+            }
+
+            if bottom_links.is_none() {
+                // Blank lines between the sections for readability
+                new_changelog.extend(std::iter::repeat_n(String::new(), config.section_spacing));
+
+                // Create new changelog entry
+                new_changelog.extend(section.iter().cloned());
+                new_changelog.push(link.clone());
+            }
+        } else {
+            new_changelog.push(line.to_string());
+        }
+    }
+    if let Some((_, link_index)) = bottom_links {
+        if link_index == old_changelog.len() {
+            new_changelog.push(link);
+        }
+    }
+
+    Ok(normalize_section_spacing(
+        new_changelog,
+        config.section_spacing,
+    ))
+}
+
+/// The heading and body of a release section together with its link line,
+/// exactly as `generate` inserts them into the changelog
+fn render_section(
+    config: &Config,
+    entry_args: &EntryArgs,
+    commits: Vec<Commit>,
+    version: &str,
+    previous_version: &str,
+    date: &str,
+) -> (Vec<String>, String) {
+    let mut section = vec![format!("# [{version}] - {date}")];
+    section.extend(render_section_body(
+        config,
+        entry_args,
+        commits,
+        version,
+        previous_version,
+        date,
+    ));
+    let link = format!(
+        "[{version}]: {}",
+        version_url(config, version, previous_version)
+    );
+
+    (section, link)
+}
+
+/// Renders the lines between a section's heading and its link line
+fn render_section_body(
+    config: &Config,
+    entry_args: &EntryArgs,
+    mut commits: Vec<Commit>,
+    version: &str,
+    previous_version: &str,
+    date: &str,
+) -> Vec<String> {
+    let mut body = vec![String::from("")];
+
+    if entry_args.expand_squash {
+        commits = expand_squash_merges(commits);
+    }
+
+    // The log is read oldest first, which every ordering below builds on
+    if entry_args.newest_first {
+        commits.reverse();
+    }
+
+    // Breaking changes are called out before all other sections. Without
+    // grouping there are no sections to put them in.
+    let breaking_changes = breaking_changes(&commits);
+    if config.group_by != GroupBy::None && !breaking_changes.is_empty() {
+        body.push(String::from("## ⚠ BREAKING CHANGES"));
+        body.push(String::from(""));
+        for breaking_change in breaking_changes {
+            body.push(format!("{} {breaking_change}", config.bullet));
+        }
+        body.push(String::from(""));
+    }
+
+    // Add all commit entries, grouped into sections if configured
+    let sort = entry_args.sort.unwrap_or(config.sort);
+    for group in group_commits(config, sort, commits) {
+        if let Some(title) = &group.title {
+            match config.section_prefixes.get(title) {
+                Some(prefix) => body.push(format!("## {prefix} {title}")),
+                None => body.push(format!("## {title}")),
+            }
+            body.push(String::from(""));
+        }
+
+        for commit in &group.commits {
+            body.push(format!(
+                "{} {}",
+                config.bullet,
+                render_entry(config, entry_args, commit)
+            ));
+
+            if entry_args.include_body && !commit.body.is_empty() {
+                // The blank line makes the body a paragraph of the list item
+                body.push(String::from(""));
+                for body_line in commit.body.lines() {
+                    if body_line.trim().is_empty() {
+                        body.push(String::from(""));
+                    } else {
+                        body.push(format!("  {body_line}"));
+                    }
+                }
+            }
+        }
+
+        body.push(String::from(""));
+    }
+
+    // Append the footer, if configured, to the end of the section
+    if let Some(footer) = &config.footer {
+        let footer = render_template(
+            footer,
+            &[
+                ("version", version),
+                ("previous_version", previous_version),
+                ("date", date),
+            ],
+        );
+        for footer_line in footer.trim_end().lines() {
+            body.push(footer_line.to_string());
+        }
+        body.push(String::from(""));
+    }
+
+    body
+}
+
+/// Replaces each squash merge, whose subject ends with a `(#123)` reference,
+/// with one commit per `* ` or `- ` bullet in its body. The bullets are the
+/// subjects of the squashed commits, so they keep the squash merge's hash and
+/// author. Squash merges without bullets are kept as they are.
+fn expand_squash_merges(commits: Vec<Commit>) -> Vec<Commit> {
+    let squash_pattern = Regex::new(r"\(#\d+\)$").unwrap();
+
+    commits
+        .into_iter()
+        .flat_map(|commit| {
+            let bullets: Vec<&str> = if squash_pattern.is_match(commit.msg) {
+                commit
+                    .body
+                    .lines()
+                    .filter_map(|line| {
+                        line.strip_prefix("* ")
+                            .or_else(|| line.strip_prefix("- "))
+                            .map(str::trim)
+                    })
+                    .filter(|bullet| !bullet.is_empty())
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            if bullets.is_empty() {
+                return vec![commit];
+            }
+            bullets
+                .into_iter()
+                .map(|bullet| Commit {
+                    msg: bullet,
+                    wrapped: "",
+                    body: "",
+                    ..commit
+                })
+                .collect()
         })
+        .collect()
 }
 
-fn get_last_version() -> String {
-    // We assume that the user has not modified the version.txt file yet
-    let file: fs::File = fs::File::open("version.txt").expect("Unable to open version text file!");
+/// Text of the bullet for the commit, with all the entry options applied
+fn render_entry(config: &Config, entry_args: &EntryArgs, commit: &Commit) -> String {
+    let mut commit_msg = entry_text(config, commit);
+    if entry_args.join_wrapped_subjects {
+        for line in commit.wrapped.lines() {
+            commit_msg.push(' ');
+            commit_msg.push_str(line.trim());
+        }
+    }
+    if entry_args.strip_ticket_prefix {
+        commit_msg = strip_ticket_prefix(config, &commit_msg);
+    }
+    if let Some(max_length) = entry_args.max_subject_length {
+        commit_msg = truncate_entry(&commit_msg, max_length);
+    }
+    if entry_args.footer_issues {
+        let issues = footer_issues(&config.issue_footers, commit.body);
+        if !issues.is_empty() {
+            let issues: Vec<String> = issues.iter().map(|issue| format!("#{issue}")).collect();
+            commit_msg.push_str(&format!(" ({})", issues.join(", ")));
+        }
+    }
+    commit_msg = link_issues(config, &commit_msg);
+    if entry_args.show_hash && !commit.hash.is_empty() {
+        let short_hash = &commit.hash[..commit.hash.len().min(7)];
+        commit_msg.push_str(&format!(
+            " ([{short_hash}]({}))",
+            commit_url(config, commit.hash)
+        ));
+    }
+
+    commit_msg
+}
+
+/// Removes the leading ticket key from the entry, appending a link to the
+/// ticket instead if a ticket URL is configured
+fn strip_ticket_prefix(config: &Config, entry: &str) -> String {
+    // The pattern is validated when the config is loaded
+    let pattern = Regex::new(&config.ticket_pattern).unwrap();
 
-    let mut buffer = BufReader::new(file);
-    let mut version_line = String::new();
-    buffer
-        .read_line(&mut version_line)
-        .expect("Cannot read line from version string buffer!");
+    let Some(ticket) = pattern.find(entry).filter(|ticket| ticket.start() == 0) else {
+        return entry.to_string();
+    };
+    let key = ticket
+        .as_str()
+        .trim_end_matches(|c: char| c == ':' || c.is_whitespace());
+    let stripped = &entry[ticket.end()..];
 
-    version_line.trim().to_string()
+    match &config.ticket_url {
+        Some(ticket_url) if !key.is_empty() => format!("{stripped} ([{key}]({ticket_url}{key}))"),
+        _ => stripped.to_string(),
+    }
 }
 
-fn push() {
-    let version = get_last_version();
+/// Shortens the entry to at most `max_length` characters, ending it with an
+/// ellipsis. A trailing `(#123)` reference is kept and doesn't count towards
+/// the length.
+fn truncate_entry(entry: &str, max_length: usize) -> String {
+    let reference_pattern = Regex::new(r"\s*\(#\d+\)$").unwrap();
 
-    let changes = get_changes(&version);
+    let (text, reference) = match reference_pattern.find(entry) {
+        Some(reference) => (&entry[..reference.start()], reference.as_str()),
+        None => (entry, ""),
+    };
 
-    Command::new("git")
-        .arg("commit")
-        .arg("-m")
-        .arg(format!("release: {version}\n\n{changes}"))
-        .status()
-        .expect("Failed to git commit");
-    Command::new("git")
-        .arg("tag")
-        .arg(version)
-        .status()
-        .expect("Failed to tag last git commit");
+    // Counting chars rather than bytes keeps multibyte characters intact
+    if text.chars().count() <= max_length {
+        return entry.to_string();
+    }
 
-    Command::new("git")
-        .arg("push")
-        .status()
-        .expect("Failed to push release to GitHub");
-    Command::new("git")
-        .arg("push")
-        .arg("--tags")
-        .status()
-        .expect("Failed to push tag to GitHub");
+    let truncated: String = text.chars().take(max_length.saturating_sub(1)).collect();
+    format!("{}…{reference}", truncated.trim_end())
 }
 
-fn get_changes(version: &str) -> String {
-    let changelog_content =
-        fs::read_to_string(CHANGELOG_FILE_NAME).expect("Cannot read the changelog file to memory!");
+fn regenerate(
+    config: &Config,
+    entry_args: &EntryArgs,
+    version: &str,
+    dedup: bool,
+) -> Result<(), Error> {
+    if Version::parse(version).is_err() {
+        return Err(Error::Usage(format!(
+            "{version} is not a valid semantic version!"
+        )));
+    }
 
-    println!("Got version: {}", version);
+    let tag = config.tag_name(version);
+    if !tag_exists(&tag)? {
+        return Err(Error::Other(format!(
+            "The tag {tag} doesn't exist! Only tagged releases can be regenerated."
+        )));
+    }
 
-    let start_marker = format!("# [{version}] - ");
-    let end_marker = format!("[{version}]: https://github.com/shipperstack/shipper/compare/");
+    let Some(previous_version) = get_previous_version(config, version, true) else {
+        return Err(Error::Other(format!(
+            "Unable to find a release tag before {version}!"
+        )));
+    };
 
-    let mut extracted_changes = String::new();
-    let mut is_in_target_version_section = false;
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.split('\n').collect();
 
-    for line in changelog_content.lines() {
-        if line.starts_with(&start_marker) {
-            is_in_target_version_section = true;
-            continue;
-        } else if line.starts_with(&end_marker) {
-            break;
-        }
+    let sections = parse_sections(&lines);
+    let Some(section) = find_section(&sections, version) else {
+        return Err(Error::Other(format!(
+            "Unable to find the section for {version} in the changelog!"
+        )));
+    };
+    let body_end = section_body_end(&lines, section);
 
-        if is_in_target_version_section && !line.starts_with('#') {
-            extracted_changes.push_str(line);
-            extracted_changes.push('\n');
-        }
+    let previous_tag = config.tag_name(&previous_version);
+    let all_commits = read_commits(&previous_tag, &tag)?;
+
+    // The tagged release commit itself shouldn't be listed in its own section
+    let mut commits = all_commits
+        .iter()
+        .map(OwnedCommit::as_commit)
+        .filter(|commit| !is_release_commit(config, commit))
+        .collect();
+    if dedup {
+        commits = dedup_against_history(config, &lines, version, commits);
     }
 
-    extracted_changes
+    let date = section.date.clone().unwrap_or_else(today_iso8601);
+
+    let mut new_changelog: Vec<String> = lines[..=section.heading]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    new_changelog.extend(render_section_body(
+        config,
+        entry_args,
+        commits,
+        version,
+        &previous_version,
+        &date,
+    ));
+    new_changelog.extend(lines[body_end..].iter().map(|line| line.to_string()));
+
+    write_file(Path::new(CHANGELOG_FILE_NAME), &new_changelog.join("\n"))?;
+
+    println!("Regenerated the section for {version} from {previous_tag}...{tag}.");
+
+    Ok(())
+}
+
+fn diff(
+    config: &Config,
+    entry_args: &EntryArgs,
+    from: &str,
+    to: &str,
+    from_git: bool,
+) -> Result<(), Error> {
+    let (Ok(from_version), Ok(to_version)) = (Version::parse(from), Version::parse(to)) else {
+        return Err(Error::Usage(format!(
+            "Both {from} and {to} must be valid semantic versions!"
+        )));
+    };
+    if from_version >= to_version {
+        return Err(Error::Usage(format!("{from} must be older than {to}!")));
+    }
+
+    if from_git {
+        let (from_tag, to_tag) = (config.tag_name(from), config.tag_name(to));
+        for tag in [&from_tag, &to_tag] {
+            if !tag_exists(tag)? {
+                return Err(Error::Other(format!("The tag {tag} doesn't exist!")));
+            }
+        }
+
+        let all_commits = read_commits(&from_tag, &to_tag)?;
+        let commits = all_commits
+            .iter()
+            .map(OwnedCommit::as_commit)
+            .filter(|commit| !is_release_commit(config, commit))
+            .collect();
+        let body = render_section_body(config, entry_args, commits, to, from, &today_iso8601());
+        println!("{}", body.join("\n").trim());
+        return Ok(());
+    }
+
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.split('\n').collect();
+
+    let sections: Vec<String> = parse_sections(&lines)
+        .iter()
+        .filter(|section| {
+            Version::parse(&section.version)
+                .is_ok_and(|version| from_version < version && version <= to_version)
+        })
+        .map(|section| {
+            lines[section.heading..section.end]
+                .join("\n")
+                .trim()
+                .to_string()
+        })
+        .collect();
+
+    if sections.is_empty() {
+        return Err(Error::Other(format!(
+            "The changelog has no releases after {from} up to {to}!"
+        )));
+    }
+
+    println!("{}", sections.join("\n\n"));
+
+    Ok(())
+}
+
+fn plan(config: &Config, version: &str) -> Result<(), Error> {
+    let Ok(planned_version) = Version::parse(version) else {
+        return Err(Error::Usage(format!(
+            "{version} is not a valid semantic version!"
+        )));
+    };
+
+    let last_version = get_last_version()?;
+    if Version::parse(&last_version).is_ok_and(|last_version| planned_version <= last_version) {
+        return Err(Error::Usage(format!(
+            "{version} must be newer than the current version {last_version}!"
+        )));
+    }
+
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.split('\n').collect();
+
+    if find_section(&parse_sections(&lines), version).is_some() {
+        return Err(Error::Other(format!(
+            "The changelog already has a section for {version}!"
+        )));
+    }
+
+    // The date is filled in when the version is actually released
+    let section = vec![format!("# [{version}] - TBD"), String::from("")];
+    let link = format!(
+        "[{version}]: {}",
+        version_url(config, version, &last_version)
+    );
+
+    let new_changelog = insert_section(config, &lines, version, section, link, false)?;
+
+    write_file(Path::new(CHANGELOG_FILE_NAME), &new_changelog.join("\n"))?;
+
+    println!("Added a placeholder section for {version}.");
+
+    Ok(())
+}
+
+/// Text of every bullet in the changelog lines, without the bullet marker
+fn bullet_texts(changes: &str) -> Vec<String> {
+    changes
+        .lines()
+        .filter_map(|line| {
+            BULLET_MARKERS
+                .iter()
+                .find_map(|marker| line.strip_prefix(&format!("{marker} ")))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether the entry is the text, maybe followed by appended links or issue references
+fn is_entry_of(entry: &str, text: &str) -> bool {
+    entry
+        .strip_prefix(text)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Whether the entry lists the commit, with or without its conventional commit type
+fn is_entry_of_commit(config: &Config, entry: &str, commit: &Commit) -> bool {
+    is_entry_of(entry, &entry_text(config, commit)) || is_entry_of(entry, commit.msg)
+}
+
+/// Leaves out the commits that one of the release sections older than the
+/// version already lists. Without a section for the version, every release
+/// section counts.
+fn dedup_against_history<'a>(
+    config: &Config,
+    lines: &[&str],
+    version: &str,
+    commits: Vec<Commit<'a>>,
+) -> Vec<Commit<'a>> {
+    let sections = parse_sections(lines);
+    let start = sections
+        .iter()
+        .position(|section| section.version == version)
+        .map_or(0, |index| index + 1);
+    let released: Vec<String> = sections[start..]
+        .iter()
+        .filter(|section| section.version != config.unreleased_heading)
+        .flat_map(|section| {
+            bullet_texts(&lines[section.heading + 1..section_body_end(lines, section)].join("\n"))
+        })
+        .collect();
+
+    let (listed, commits): (Vec<Commit>, Vec<Commit>) = commits.into_iter().partition(|commit| {
+        released
+            .iter()
+            .any(|entry| is_entry_of_commit(config, entry, commit))
+    });
+    if !listed.is_empty() {
+        println!(
+            "Left out {} commits that an older release already lists.",
+            listed.len()
+        );
+    }
+
+    commits
+}
+
+fn audit(config: &Config, version: Option<&str>) -> Result<(), Error> {
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.split('\n').collect();
+
+    let versions: Vec<String> = parse_sections(&lines)
+        .into_iter()
+        .map(|section| section.version)
+        .filter(|version| *version != config.unreleased_heading)
+        .collect();
+    let index = match version {
+        Some(version) => versions.iter().position(|listed| listed == version),
+        None => (!versions.is_empty()).then_some(0),
+    }
+    .ok_or_else(|| {
+        Error::Other(format!(
+            "The changelog has no section for {}!",
+            version.unwrap_or("any release")
+        ))
+    })?;
+    let version = &versions[index];
+    let Some(previous_version) = versions.get(index + 1) else {
+        return Err(Error::Other(format!(
+            "{version} is the oldest release in the changelog, so it has no range to audit!"
+        )));
+    };
+
+    // A release that was generated but not pushed yet ends at HEAD
+    let tag = config.tag_name(version);
+    let to = if tag_exists(&tag)? {
+        tag
+    } else {
+        String::from("HEAD")
+    };
+    let from = config.tag_name(previous_version);
+
+    let entries = bullet_texts(&get_changes(version)?);
+    let all_commits = read_commits(&from, &to)?;
+    let commits: Vec<Commit> = all_commits
+        .iter()
+        .map(OwnedCommit::as_commit)
+        .filter(|commit| !is_release_commit(config, commit))
+        .collect();
+
+    let breaking_changes = breaking_changes(&commits);
+
+    println!("Audit of {version} ({from}...{to}):");
+    let mut discrepancies = 0;
+    for entry in &entries {
+        let has_commit = commits
+            .iter()
+            .any(|commit| is_entry_of_commit(config, entry, commit))
+            || breaking_changes
+                .iter()
+                .any(|breaking_change| is_entry_of(entry, breaking_change));
+        if !has_commit {
+            println!("- {entry}");
+            discrepancies += 1;
+        }
+    }
+    for commit in &commits {
+        if !entries
+            .iter()
+            .any(|entry| is_entry_of_commit(config, entry, commit))
+        {
+            println!(
+                "+ {} {}",
+                &commit.hash[..commit.hash.len().min(7)],
+                commit.msg
+            );
+            discrepancies += 1;
+        }
+    }
+
+    if discrepancies > 0 {
+        return Err(Error::Other(format!(
+            "{discrepancies} discrepancies between the section for {version} and its commits! \
+Lines starting with - are entries without a commit, and + are commits without an entry."
+        )));
+    }
+
+    println!("Every entry matches a commit, and every commit has an entry.");
+    Ok(())
+}
+
+fn list(config: &Config, format: ListFormat) -> Result<(), Error> {
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let lines: Vec<&str> = changelog.split('\n').collect();
+
+    let sections: Vec<_> = parse_sections(&lines)
+        .into_iter()
+        .filter(|section| section.version != config.unreleased_heading)
+        .collect();
+
+    match format {
+        ListFormat::Json => {
+            let versions: Vec<serde_json::Value> = sections
+                .iter()
+                .map(|section| {
+                    // Planned sections have a placeholder like TBD instead of a date
+                    let date = section
+                        .date
+                        .as_deref()
+                        .filter(|date| parse_iso8601_date(date).is_ok());
+                    serde_json::json!({ "version": section.version, "date": date })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&versions).unwrap());
+        }
+        ListFormat::Table => {
+            let width = sections
+                .iter()
+                .map(|section| section.version.len())
+                .max()
+                .unwrap_or(0);
+            for section in &sections {
+                let date = section.date.as_deref().unwrap_or("-");
+                println!("{:width$}  {date}", section.version);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn notes(config: &Config, args: &NotesArgs) -> Result<(), Error> {
+    if args.github_style && args.format != NotesFormat::Markdown {
+        return Err(Error::Usage(String::from(
+            "--github-style only applies to the markdown format!",
+        )));
+    }
+
+    for git_ref in [&args.from, &args.to] {
+        if !ref_exists(git_ref)? {
+            return Err(Error::Git(format!("The ref {git_ref} doesn't exist!")));
+        }
+    }
+
+    let all_commits = read_commits(&args.from, &args.to)?;
+    let commits = filter_commits(
+        &args.filter,
+        all_commits
+            .iter()
+            .map(OwnedCommit::as_commit)
+            .filter(|commit| !is_release_commit(config, commit)),
+    );
+
+    let notes = match args.format {
+        NotesFormat::Json => {
+            let notes = notes_json(config, &args.entry, commits, &args.from, &args.to);
+            format!("{}\n", serde_json::to_string_pretty(&notes).unwrap())
+        }
+        format => {
+            let mut body = render_section_body(
+                config,
+                &args.entry,
+                commits,
+                &args.to,
+                &args.from,
+                &today_iso8601(),
+            );
+            if format == NotesFormat::Plain {
+                body = body.iter().map(|line| strip_markdown(line)).collect();
+            }
+            if args.github_style {
+                github_style_notes(config, body, &args.from, &args.to)
+            } else {
+                format!("{}\n", body.join("\n").trim())
+            }
+        }
+    };
+
+    match &args.output {
+        Some(output) => {
+            if write_output(output, &notes)? {
+                println!(
+                    "Notes for {}...{} written to {}.",
+                    args.from,
+                    args.to,
+                    output.display()
+                );
+            }
+        }
+        None => print!("{notes}"),
+    }
+
+    Ok(())
+}
+
+/// Puts the notes under a `## What's Changed` heading, with their own section
+/// headings a level below it, followed by a link comparing the two refs
+fn github_style_notes(config: &Config, body: Vec<String>, from: &str, to: &str) -> String {
+    let body: Vec<String> = body
+        .into_iter()
+        .map(|line| match line.strip_prefix("## ") {
+            Some(heading) => format!("### {heading}"),
+            None => line,
+        })
+        .collect();
+
+    format!(
+        "## What's Changed\n\n{}\n\n**Full Changelog**: {}\n",
+        body.join("\n").trim(),
+        compare_url(config, from, to)
+    )
+}
+
+/// The notes as JSON, with the same sections and entries as the Markdown
+fn notes_json(
+    config: &Config,
+    entry_args: &EntryArgs,
+    mut commits: Vec<Commit>,
+    from: &str,
+    to: &str,
+) -> serde_json::Value {
+    if entry_args.expand_squash {
+        commits = expand_squash_merges(commits);
+    }
+    if entry_args.newest_first {
+        commits.reverse();
+    }
+
+    let breaking_changes = breaking_changes(&commits);
+    let sort = entry_args.sort.unwrap_or(config.sort);
+    let sections: Vec<serde_json::Value> = group_commits(config, sort, commits)
+        .iter()
+        .map(|group| {
+            let entries: Vec<serde_json::Value> = group
+                .commits
+                .iter()
+                .map(|commit| {
+                    serde_json::json!({
+                        "text": render_entry(config, entry_args, commit),
+                        "subject": commit.msg,
+                        "body": commit.body,
+                        "hash": commit.hash,
+                        "author": commit.author,
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "title": group.title,
+                "count": entries.len(),
+                "entries": entries,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "from": from,
+        "to": to,
+        "breaking_changes": breaking_changes,
+        "sections": sections,
+    })
+}
+
+/// Warns about every commit whose conventional commit scope isn't one of the
+/// allowed scopes, if any are configured. The commits are still listed.
+fn warn_unknown_scopes(config: &Config, commits: &[Commit]) {
+    if config.allowed_scopes.is_empty() {
+        return;
+    }
+
+    for commit in commits {
+        let Some(scope) =
+            parse_conventional(commit.msg).and_then(|conventional| conventional.scope)
+        else {
+            continue;
+        };
+        if !scope.is_empty() && !config.allowed_scopes.iter().any(|allowed| allowed == scope) {
+            println!(
+                "Warning: the scope {scope:?} of \"{}\" isn't one of the allowed scopes: {}",
+                commit.msg,
+                config.allowed_scopes.join(", ")
+            );
+        }
+    }
+}
+
+/// Warns if the tag points at another commit on the remote than locally, e.g.
+/// because it was moved with a force push, since the range would then differ
+fn check_remote_tag(config: &Config, tag: &str) {
+    let remote = config.remotes.first().map_or("origin", String::as_str);
+    let tag_ref = format!("refs/tags/{tag}");
+
+    let Ok(local) = run_git(
+        git::command()
+            .arg("rev-parse")
+            .arg(format!("{tag_ref}^{{commit}}")),
+        &format!("Failed to resolve {tag}!"),
+    ) else {
+        return;
+    };
+    let local = String::from_utf8_lossy(&local.stdout).trim().to_string();
+
+    let remote_output = match run_git(
+        git::command()
+            .arg("ls-remote")
+            .arg("--tags")
+            .arg(remote)
+            .arg(&tag_ref),
+        &format!("Failed to list the tags of {remote}!"),
+    ) {
+        Ok(output) => output,
+        Err(e) => {
+            println!("Warning: unable to check the tag {tag} on {remote}: {e}");
+            return;
+        }
+    };
+
+    // Annotated tags are listed twice, and the `^{}` line is the commit
+    let remote_refs = String::from_utf8_lossy(&remote_output.stdout).into_owned();
+    let remote_commit = ["^{}", ""].iter().find_map(|suffix| {
+        remote_refs.lines().find_map(|line| {
+            let (hash, name) = line.split_once('\t')?;
+            (name == format!("{tag_ref}{suffix}")).then(|| hash.to_string())
+        })
+    });
+
+    match remote_commit {
+        Some(remote_commit) if remote_commit != local => println!(
+            "Warning: the tag {tag} points at {} on {remote} but at {} locally! It may have \
+been moved with a force push, so the release may not cover the right commits. Run `git fetch \
+--tags --force` to update the local tag.",
+            &remote_commit[..remote_commit.len().min(7)],
+            &local[..local.len().min(7)]
+        ),
+        Some(_) => {}
+        None => println!("Warning: the tag {tag} doesn't exist on {remote}."),
+    }
+}
+
+/// The range with the commits it resolves to and the hashes of the commits
+/// the new section lists, as written by `--commit-range-file`
+fn commit_range_record(
+    from: &str,
+    to: &str,
+    commits: &[Commit],
+) -> Result<serde_json::Value, Error> {
+    let resolve = |revision: &str| -> Result<String, Error> {
+        let output = run_git(
+            git::command()
+                .arg("rev-parse")
+                .arg(format!("{revision}^{{commit}}")),
+            &format!("Failed to resolve {revision}!"),
+        )?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    // Manual entries have no commit
+    let hashes: Vec<&str> = commits
+        .iter()
+        .map(|commit| commit.hash)
+        .filter(|hash| !hash.is_empty())
+        .collect();
+
+    Ok(serde_json::json!({
+        "from": from,
+        "to": to,
+        "from_sha": resolve(from)?,
+        "to_sha": resolve(to)?,
+        "commits": hashes,
+    }))
+}
+
+/// The bump of `--bump auto`, from the commits that the release would list
+fn detect_release_bump(
+    config: &Config,
+    args: &GenerateArgs,
+    last_version: &str,
+) -> Result<BumpKind, Error> {
+    let (from, to) = compute_range(
+        config,
+        &RangeArgs {
+            from: Some(config.tag_name(last_version)),
+            ..RangeArgs::default()
+        },
+    )?;
+    let all_commits = read_commits(&from, &to)?;
+    let commits = filter_commits(
+        &args.filter,
+        all_commits
+            .iter()
+            .map(OwnedCommit::as_commit)
+            .filter(|commit| !is_release_commit(config, commit)),
+    );
+
+    let bump_kind = detect_bump(&commits, config.min_bump).ok_or_else(|| {
+        Error::NothingToRelease(format!(
+            "No features, fixes or breaking changes since {last_version}, and min_bump is none; \
+nothing to release."
+        ))
+    })?;
+    println!("Detected a {bump_kind} bump");
+
+    Ok(bump_kind)
+}
+
+/// A summary of how many entries each section has, e.g. `Features: 3, Other: 2`
+fn section_stats(config: &Config, entry_args: &EntryArgs, mut commits: Vec<Commit>) -> String {
+    if entry_args.expand_squash {
+        commits = expand_squash_merges(commits);
+    }
+
+    let breaking_changes = breaking_changes(&commits).len();
+    let sort = entry_args.sort.unwrap_or(config.sort);
+    let mut counts: Vec<String> = group_commits(config, sort, commits)
+        .iter()
+        .map(|group| match &group.title {
+            Some(title) => format!("{title}: {}", group.commits.len()),
+            None => format!("Entries: {}", group.commits.len()),
+        })
+        .collect();
+    if config.group_by != GroupBy::None && breaking_changes > 0 {
+        counts.push(format!("Breaking changes: {breaking_changes}"));
+    }
+    if counts.is_empty() {
+        counts.push(String::from("Entries: 0"));
+    }
+
+    counts.join(", ")
+}
+
+/// Removes the Markdown syntax the notes use: headings, bold text and links
+fn strip_markdown(line: &str) -> String {
+    let link_pattern = Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap();
+
+    let line = line.trim_start_matches("## ").replace("**", "");
+    link_pattern.replace_all(&line, "$1").to_string()
+}
+
+/// Whether the commit's subject matches the subject of the release commit template
+fn is_release_commit(config: &Config, commit: &Commit) -> bool {
+    let subject_template = config.commit_message.lines().next().unwrap_or_default();
+    let pattern = regex::escape(subject_template)
+        .replace(r"\{version\}", r"\S+")
+        .replace(r"\{date\}", r"\S+");
+
+    Regex::new(&format!("^{pattern}$")).is_ok_and(|pattern| pattern.is_match(commit.msg))
+}
+
+/// Finds the highest version below the given version that has a release tag,
+/// optionally skipping prereleases
+fn get_previous_version(
+    config: &Config,
+    version: &str,
+    include_prereleases: bool,
+) -> Option<String> {
+    let version = Version::parse(version).ok()?;
+
+    let tag_output = run_git(
+        git::command().arg("tag").arg("--list"),
+        "Failed to list git tags!",
+    )
+    .ok()?;
+
+    String::from_utf8_lossy(&tag_output.stdout)
+        .lines()
+        .filter_map(|tag| tag.trim().strip_prefix(&config.tag_prefix))
+        .filter_map(|tag_version| Some((Version::parse(tag_version).ok()?, tag_version)))
+        .filter(|(tag_version, _)| *tag_version < version)
+        .filter(|(tag_version, _)| include_prereleases || tag_version.pre.is_empty())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag_version)| tag_version.to_string())
+}
+
+/// The prefix of the release tags, taken from the tags that end in a semantic
+/// version. Without such tags, or when they use different prefixes, there is
+/// no prefix.
+fn detect_tag_prefix() -> Result<String, Error> {
+    let output = run_git(
+        git::command().arg("tag").arg("--list"),
+        "Failed to list git tags!",
+    )?;
+    let tag_pattern = Regex::new(r"^(\S*?)(\d+\.\d+\.\d+\S*)$").unwrap();
+
+    let mut prefixes: Vec<String> = Vec::new();
+    for tag in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(cap) = tag_pattern.captures(tag.trim()) else {
+            continue;
+        };
+        if Version::parse(&cap[2]).is_ok() && !prefixes.iter().any(|prefix| prefix == &cap[1]) {
+            prefixes.push(cap[1].to_string());
+        }
+    }
+
+    match prefixes.as_slice() {
+        [prefix] => Ok(prefix.clone()),
+        [] => Ok(String::new()),
+        _ => {
+            // Notes may be printed to stdout, so the warning goes to stderr
+            eprintln!(
+                "Warning: the release tags use several prefixes ({}), so no tag prefix is used.",
+                prefixes
+                    .iter()
+                    .map(|prefix| format!("{prefix:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Ok(String::new())
+        }
+    }
+}
+
+fn fix_links(config: &Config) -> Result<(), Error> {
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+    let mut lines: Vec<String> = changelog.split('\n').map(String::from).collect();
+
+    let sections = parse_sections(&lines);
+    let versions: Vec<&str> = sections
+        .iter()
+        .map(|section| section.version.as_str())
+        .filter(|version| *version != config.unreleased_heading)
+        .collect();
+    // Planned sections with a TBD date aren't released yet
+    let latest_release = sections
+        .iter()
+        .filter(|section| section.version != config.unreleased_heading)
+        .find(|section| {
+            section
+                .date
+                .as_deref()
+                .is_some_and(|date| parse_iso8601_date(date).is_ok())
+        })
+        .map(|section| section.version.as_str());
+
+    let link_pattern = Regex::new(r"^\[([^\]]+)\]: ").unwrap();
+    let compare_url_prefix = compare_url_prefix(config);
+    let preamble_end = sections
+        .first()
+        .map_or(lines.len(), |section| section.heading);
+
+    let mut fixed = 0;
+    for line in lines.iter_mut().skip(preamble_end) {
+        let Some(label) = link_pattern.captures(line).map(|cap| cap[1].to_string()) else {
+            continue;
+        };
+        // Links to a tree or release page don't depend on the other versions
+        if !line.starts_with(&format!("[{label}]: {compare_url_prefix}")) {
+            continue;
+        }
+
+        let url = if label == config.unreleased_anchor() {
+            let Some(latest_release) = latest_release else {
+                continue;
+            };
+            compare_url(config, &config.tag_name(latest_release), "HEAD")
+        } else {
+            // The oldest release has nothing to compare against
+            let Some(previous_version) = versions
+                .iter()
+                .position(|version| *version == label)
+                .and_then(|index| versions.get(index + 1))
+            else {
+                continue;
+            };
+            compare_url(
+                config,
+                &config.tag_name(previous_version),
+                &config.tag_name(&label),
+            )
+        };
+
+        let fixed_line = format!("[{label}]: {url}");
+        if *line != fixed_line {
+            println!("Fixed the link of {label}:\n  - {line}\n  + {fixed_line}");
+            *line = fixed_line;
+            fixed += 1;
+        }
+    }
+
+    if fixed == 0 {
+        println!("All compare links are already consistent.");
+        return Ok(());
+    }
+
+    write_file(Path::new(CHANGELOG_FILE_NAME), &lines.join("\n"))?;
+
+    println!("Fixed {fixed} links.");
+
+    Ok(())
+}
+
+fn amend_date(config: &Config) -> Result<(), Error> {
+    let changelog = read_file(Path::new(CHANGELOG_FILE_NAME))?;
+
+    let mut lines: Vec<String> = changelog.split('\n').map(String::from).collect();
+
+    // The topmost section heading that isn't the Unreleased one. A section
+    // planned with `plan` isn't released yet, so it keeps its placeholder.
+    let Some(section) = parse_sections(&lines)
+        .into_iter()
+        .find(|section| section.version != config.unreleased_heading && !is_planned(section))
+        .filter(|section| section.date.is_some() && Version::parse(&section.version).is_ok())
+    else {
+        return Err(Error::Other(String::from(
+            "Unable to find a version section at the top of the changelog!",
+        )));
+    };
+    let (index, version) = (section.heading, section.version);
+
+    let today_iso8601 = today_iso8601();
+
+    lines[index] = format!("# [{version}] - {today_iso8601}");
+
+    write_file(Path::new(CHANGELOG_FILE_NAME), &lines.join("\n"))?;
+
+    println!("Updated the date of {version} to {today_iso8601}.");
+
+    Ok(())
+}
+
+/// Computes the `from...to` commit range, defaulting to everything since the
+/// tag of the current version
+fn compute_range(config: &Config, args: &RangeArgs) -> Result<(String, String), Error> {
+    let to = args.to.clone().unwrap_or_else(|| String::from("HEAD"));
+
+    let from = if args.since_tag {
+        let describe_output = query_git(
+            git::command()
+                .arg("describe")
+                .arg("--tags")
+                .arg("--abbrev=0")
+                .arg(&to),
+            &format!("Failed to find the tag reachable from {to}!"),
+        )?;
+        if !describe_output.status.success() {
+            return Err(Error::Other(format!(
+                "Unable to find a tag reachable from {to}!"
+            )));
+        }
+        String::from_utf8_lossy(&describe_output.stdout)
+            .trim()
+            .to_string()
+    } else {
+        match &args.from {
+            Some(from) => from.clone(),
+            None => config.tag_name(&get_last_version()?),
+        }
+    };
+
+    Ok((from, to))
+}
+
+/// Whether `to` has any commits that `from` doesn't. If git can't tell, for
+/// example because `from` doesn't exist, this assumes there are.
+fn has_commits_since(from: &str, to: &str) -> Result<bool, Error> {
+    let output = query_git(
+        git::command()
+            .arg("rev-list")
+            .arg("--count")
+            .arg(format!("{from}..{to}")),
+        &format!("Failed to count the commits of {from}..{to}!"),
+    )?;
+
+    Ok(!output.status.success() || String::from_utf8_lossy(&output.stdout).trim() != "0")
+}
+
+fn get_git_log_raw(from: &str, to: &str) -> Result<String, Error> {
+    // Get git log between the two revisions
+    let git_log_output = run_git(
+        git::command()
+            .arg("log")
+            // Fields are separated by the unit separator and commits by the
+            // record separator, since messages may contain anything else. The raw
+            // message is read rather than `%s`, which joins a wrapped subject.
+            .arg("--format=%H%x1f%an <%ae>%x1f%B%x1e")
+            .arg("--reverse")
+            .arg(format!("{from}...{to}")),
+        &format!("Failed to read the git log of {from}...{to}!"),
+    )?;
+
+    // Commit messages with invalid UTF-8 shouldn't crash the whole run
+    Ok(String::from_utf8_lossy(&git_log_output.stdout).into_owned())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Commit<'a> {
+    hash: &'a str,
+    author: &'a str,
+    /// The first line of the subject
+    msg: &'a str,
+    /// The lines the subject wraps onto before the first blank line, if any
+    wrapped: &'a str,
+    body: &'a str,
+}
+
+/// A parsed commit owning its fields, so it can be kept after the raw git
+/// log is dropped. Borrow it as a [`Commit`] for grouping and rendering.
+#[derive(Clone, Debug, PartialEq)]
+struct OwnedCommit {
+    hash: String,
+    author: String,
+    msg: String,
+    wrapped: String,
+    body: String,
+}
+
+impl OwnedCommit {
+    fn as_commit(&self) -> Commit<'_> {
+        Commit {
+            hash: &self.hash,
+            author: &self.author,
+            msg: &self.msg,
+            wrapped: &self.wrapped,
+            body: &self.body,
+        }
+    }
+}
+
+impl From<Commit<'_>> for OwnedCommit {
+    fn from(commit: Commit) -> Self {
+        OwnedCommit {
+            hash: commit.hash.to_string(),
+            author: commit.author.to_string(),
+            msg: commit.msg.to_string(),
+            wrapped: commit.wrapped.to_string(),
+            body: commit.body.to_string(),
+        }
+    }
+}
+
+/// Reads and parses the commits between the two revisions
+fn read_commits(from: &str, to: &str) -> Result<Vec<OwnedCommit>, Error> {
+    Ok(parse_git_log_owned(&get_git_log_raw(from, to)?))
+}
+
+/// Like [`parse_git_log`], but the commits don't borrow from the input
+fn parse_git_log_owned(stdout: &str) -> Vec<OwnedCommit> {
+    parse_git_log(stdout).map(OwnedCommit::from).collect()
+}
+
+fn parse_git_log(stdout: &str) -> impl Iterator<Item = Commit<'_>> + '_ {
+    let pattern = Regex::new(
+        r"(?xs)
+            ^([0-9a-fA-F]{7,64}) # commit hash, abbreviated or full
+            \x1f
+            ([^\x1f]*)     # The commit author
+            \x1f
+            (.*)           # The raw commit message",
+    )
+    .unwrap();
+    let blank_line_pattern = Regex::new(r"\n[ \t]*\n").unwrap();
+
+    stdout
+        .split('\x1e')
+        .filter_map(move |record| pattern.captures(record.trim_start()))
+        .map(move |cap| {
+            // The subject is the first paragraph, and the body everything after it
+            let message = cap.get(3).unwrap().as_str().trim();
+            let (subject, body) = match blank_line_pattern.find(message) {
+                Some(blank_line) => (&message[..blank_line.start()], &message[blank_line.end()..]),
+                None => (message, ""),
+            };
+            let (msg, wrapped) = subject.split_once('\n').unwrap_or((subject, ""));
+
+            Commit {
+                hash: cap.get(1).unwrap().as_str(),
+                author: cap.get(2).unwrap().as_str().trim(),
+                msg: msg.trim(),
+                wrapped: wrapped.trim(),
+                body: body.trim(),
+            }
+        })
+}
+
+/// Whether the author matches the pattern, either exactly (by full identity,
+/// name or email) or as a regex
+fn author_matches(pattern: &str, author: &str) -> bool {
+    let (name, email) = author
+        .strip_suffix('>')
+        .and_then(|author| author.split_once(" <"))
+        .unwrap_or((author, ""));
+
+    if pattern == author || pattern == name || pattern == email {
+        return true;
+    }
+
+    Regex::new(pattern).is_ok_and(|pattern| pattern.is_match(author))
+}
+
+fn filter_commits<'a>(
+    filter_args: &FilterArgs,
+    commits: impl Iterator<Item = Commit<'a>>,
+) -> Vec<Commit<'a>> {
+    commits
+        .filter(|commit| {
+            !filter_args
+                .exclude_author
+                .iter()
+                .any(|pattern| author_matches(pattern, commit.author))
+        })
+        .filter(|commit| {
+            filter_args.only_author.is_empty()
+                || filter_args
+                    .only_author
+                    .iter()
+                    .any(|pattern| author_matches(pattern, commit.author))
+        })
+        .collect()
+}
+
+fn get_last_version() -> Result<String, Error> {
+    // We assume that the user has not modified the version.txt file yet
+    let contents = read_file(Path::new(VERSION_FILE_NAME))?;
+
+    parse_version_file(&contents).ok_or_else(|| {
+        Error::Other(format!("Unable to find a version in {VERSION_FILE_NAME}! Add the current version on its own line."))
+    })
+}
+
+/// The new contents of the version file. Only the version is replaced, on the
+/// line [`parse_version_file`] reads it from, so comments, blank lines and the
+/// trailing newline, or its absence, are kept as they were.
+fn version_file_contents(current: &str, version: &str) -> String {
+    let mut contents = String::new();
+    let mut replaced = false;
+    for line in current.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !replaced && !trimmed.is_empty() && !trimmed.starts_with('#') {
+            contents.push_str(&line.replacen(trimmed, version, 1));
+            replaced = true;
+        } else {
+            contents.push_str(line);
+        }
+    }
+
+    // A missing or empty file only gets the version
+    if !replaced {
+        return version.to_string();
+    }
+    contents
+}
+
+/// Picks the first line that isn't blank or a `#` comment from the version file
+fn parse_version_file(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+}
+
+fn release(config: &Config, args: &ReleaseArgs) -> Result<(), Error> {
+    // The confirmation before pushing replaces the one for writing the files,
+    // but not the warning about uncommitted changes to the changelog
+    let generate_args = GenerateArgs {
+        skip_diff: true,
+        ..args.generate.clone()
+    };
+    if !generate_changelog(config, &generate_args, generate_args.bump_arg())? {
+        return Ok(());
+    }
+
+    if args.edit {
+        run_editor(Path::new(CHANGELOG_FILE_NAME))?;
+    }
+
+    let version = get_last_version()?;
+    print!("{}", get_changes(&version)?);
+    if !args.generate.yes && !confirm(&format!("Push the release {version}?"), true) {
+        println!(
+            "Not pushed. The changelog and version file are updated, so run `push` when ready."
+        );
+        return Ok(());
+    }
+
+    push(config, &args.push)
+}
+
+fn push(config: &Config, args: &PushArgs) -> Result<(), Error> {
+    if args.check {
+        return check_push(config, args);
+    }
+
+    let version = get_last_version()?;
+
+    let changes = get_changes(&version)?;
+
+    let today_iso8601 = today_iso8601();
+    let template_values = [
+        ("version", version.as_str()),
+        ("date", today_iso8601.as_str()),
+        ("changes", changes.as_str()),
+    ];
+
+    let mut commit_message = render_template(&config.commit_message, &template_values);
+
+    if args.print_commit_message {
+        println!("{commit_message}");
+        return Ok(());
+    }
+
+    // Remotes from the command line replace the configured ones
+    let remotes = if args.remote.is_empty() {
+        &config.remotes
+    } else {
+        &args.remote
+    };
+    for remote in remotes {
+        if !remote_exists(remote)? {
+            return Err(Error::Other(format!("The remote {remote} doesn't exist!")));
+        }
+    }
+
+    let signing_key = if args.sign || config.sign {
+        match get_signing_key(config)? {
+            Some(signing_key) => Some(signing_key),
+            None => {
+                return Err(Error::Other(format!(
+                    "Signing was requested but no signing key is available! Set signing_key \
+in {CONFIG_FILE_NAME} or git's user.signingkey."
+                )));
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(tag_message_file) = &args.tag_message_file {
+        let tag_message = fs::read_to_string(tag_message_file).map_err(|e| {
+            Error::Usage(format!(
+                "Unable to read the tag message file {}: {e}",
+                tag_message_file.display()
+            ))
+        })?;
+        if tag_message.trim().is_empty() {
+            return Err(Error::Usage(format!(
+                "The tag message file {} is empty!",
+                tag_message_file.display()
+            )));
+        }
+    }
+
+    if args.verify_signature && signing_key.is_none() {
+        return Err(Error::Usage(String::from(
+            "--verify-signature needs signing to be enabled with --sign or the sign config key!",
+        )));
+    }
+
+    // A previous run may have failed partway, after committing and tagging
+    let release_subject = commit_message.lines().next().unwrap_or_default();
+    let head_is_release_commit = get_head_subject()?.as_deref() == Some(release_subject);
+
+    let tag = config.tag_name(&version);
+    let skip_tag = if tag_exists(&tag)? {
+        if !head_is_release_commit || !tag_points_at_head(&tag) {
+            return Err(Error::Other(format!(
+                "The tag {tag} already exists! Did you forget to run `generate`?"
+            )));
+        }
+        println!("The tag {tag} already points at the release commit, skipping tagging.");
+        true
+    } else {
+        false
+    };
+
+    if head_is_release_commit && !args.tag_only {
+        println!("HEAD is already the release commit for {version}, skipping the commit.");
+    }
+
+    // Everything this run creates, so a failed publish can be undone
+    let mut created_commit_parent: Option<String> = None;
+    let mut created_tag = false;
+
+    if !args.tag_only && !head_is_release_commit {
+        stage_release_files(config, &version, args.commit_all)?;
+
+        // Committing nothing would fail, and tagging HEAD instead would tag an
+        // unrelated commit, e.g. when CI already committed the release files
+        if !has_staged_changes()? {
+            return Err(Error::Other(format!(
+                "Nothing is staged for the release commit of {version}! If the release \
+changes are already committed, run `push --tag-only` to tag HEAD."
+            )));
+        }
+
+        if args.edit_message {
+            commit_message = edit_commit_message(&commit_message)?;
+        }
+
+        let parent = get_head_hash()?;
+
+        let mut commit_command = signing_command(config, signing_key.is_some());
+        set_committer(&mut commit_command, args.committer.as_ref());
+        commit_command.arg("commit").arg("-m").arg(&commit_message);
+        if args.no_verify {
+            commit_command.arg("--no-verify");
+        }
+        if let Some(signing_key) = &signing_key {
+            commit_command.arg(format!("--gpg-sign={signing_key}"));
+        }
+        if let Some(author) = &args.author {
+            commit_command.arg(format!("--author={author}"));
+        }
+        let output = run_git(&mut commit_command, "Failed to create the release commit!")?;
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        created_commit_parent = parent;
+    }
+
+    let verify_command = args.verify.as_ref().or(config.verify_command.as_ref());
+    if let (false, Some(verify_command)) = (skip_tag, verify_command) {
+        if let Err(e) = run_verify_command(verify_command) {
+            if args.rollback_on_failure {
+                roll_back(&tag, false, created_commit_parent.as_deref());
+            } else if created_commit_parent.is_some() {
+                println!(
+                    "The release commit was kept for inspection. Nothing was tagged or pushed."
+                );
+            }
+            return Err(e);
+        }
+    }
+
+    if !skip_tag && !args.no_tag {
+        create_tag(
+            config,
+            &tag,
+            args.tag_message_file.as_deref(),
+            signing_key.as_deref(),
+            args.committer.as_ref(),
+            &commit_message,
+            &template_values,
+        )?;
+        created_tag = true;
+    }
+
+    let mut progress = PublishProgress::default();
+    let result = publish(config, args, &tag, remotes, &mut progress);
+
+    if result.is_err() {
+        if args.rollback_on_failure {
+            if progress.published {
+                println!("Not rolling back, since the release already reached a remote.");
+            } else {
+                roll_back(&tag, created_tag, created_commit_parent.as_deref());
+            }
+        } else if progress.auth_failed
+            && !progress.published
+            && (created_tag || created_commit_parent.is_some())
+        {
+            // Otherwise the next attempt would find the local tag and stop
+            if confirm(
+                "Git couldn't authenticate with the remote. Roll back the release commit and \
+tag created by this run?",
+                false,
+            ) {
+                roll_back(&tag, created_tag, created_commit_parent.as_deref());
+            }
+        }
+    }
+
+    result
+}
+
+/// Runs every check `push` needs to pass before releasing, printing the result
+/// of each. Fails if any of them does.
+fn check_push(config: &Config, args: &PushArgs) -> Result<(), Error> {
+    let version = get_last_version()?;
+    let tag = config.tag_name(&version);
+
+    let mut checks: Vec<(String, Result<(), String>)> = vec![
+        (
+            format!("{version} is a valid semantic version"),
+            Version::parse(&version)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            format!("The changelog has a non-empty section for {version}"),
+            get_changes(&version).map(|_| ()),
+        ),
+        (
+            String::from("HEAD is on a branch"),
+            head_is_on_branch()?
+                .then_some(())
+                .ok_or_else(|| String::from("HEAD is detached")),
+        ),
+        (
+            String::from("The working tree has no other unstaged changes"),
+            (args.commit_all || !has_unstaged_changes(config, &version)?)
+                .then_some(())
+                .ok_or_else(|| {
+                    String::from("stage the other changes with `git add`, or pass --commit-all")
+                }),
+        ),
+        (
+            format!("The tag {tag} doesn't exist yet"),
+            (!tag_exists(&tag)? || tag_points_at_head(&tag))
+                .then_some(())
+                .ok_or_else(|| String::from("did you forget to run `generate`?")),
+        ),
+    ];
+
+    let remotes = if args.remote.is_empty() {
+        &config.remotes
+    } else {
+        &args.remote
+    };
+    for remote in remotes {
+        checks.push((
+            format!("The remote {remote} exists"),
+            remote_exists(remote)?
+                .then_some(())
+                .ok_or_else(|| String::from("add it with `git remote add`")),
+        ));
+    }
+
+    if args.sign || config.sign {
+        checks.push((
+            String::from("A signing key is available"),
+            get_signing_key(config)?.map(|_| ()).ok_or_else(|| {
+                format!("set signing_key in {CONFIG_FILE_NAME} or git's user.signingkey")
+            }),
+        ));
+    }
+
+    for (description, result) in &checks {
+        match result {
+            Ok(()) => println!("ok    {description}"),
+            Err(e) => println!("FAIL  {description}: {e}"),
+        }
+    }
+
+    let failed = checks.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        return Err(Error::Other(format!(
+            "{failed} of {} push checks failed!",
+            checks.len()
+        )));
+    }
+
+    println!("All push checks passed.");
+    Ok(())
+}
+
+/// Lets the message be edited in the editor git uses for commit messages, like
+/// `git commit` does. Comment lines are dropped, and an empty message aborts.
+fn edit_commit_message(commit_message: &str) -> Result<String, Error> {
+    let path = env::temp_dir().join(format!("shipper-release-{}.txt", std::process::id()));
+    fs::write(
+        &path,
+        format!(
+            "{commit_message}\n\n# Edit the release commit message. Lines starting with # are \
+ignored, and an empty message aborts the release.\n"
+        ),
+    )
+    .expect("Failed to write the commit message file!");
+
+    let status = run_editor(&path);
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    status.map_err(|e| Error::Other(format!("{e} Nothing was committed.")))?;
+
+    let edited: Vec<&str> = edited
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    let edited = edited.join("\n").trim().to_string();
+    if edited.is_empty() {
+        return Err(Error::Other(String::from(
+            "Aborting the release because the commit message is empty. Nothing was committed.",
+        )));
+    }
+
+    Ok(edited)
+}
+
+/// Opens the file in the editor git uses for commit messages and waits for it to close
+fn run_editor(path: &Path) -> Result<(), Error> {
+    let editor_output = run_git(
+        git::command().arg("var").arg("GIT_EDITOR"),
+        "Unable to find an editor! Set $EDITOR or git's core.editor.",
+    )?;
+    let editor = String::from_utf8_lossy(&editor_output.stdout)
+        .trim()
+        .to_string();
+
+    // Git runs the editor through the shell too, so it may have arguments
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(Error::Other(format!(
+            "The editor `{editor}` failed with {status}!"
+        ))),
+        Err(e) => Err(Error::Other(format!(
+            "Unable to run the editor `{editor}`: {e}"
+        ))),
+    }
+}
+
+/// Runs the verify command through the shell, with its output going straight
+/// to the terminal
+fn run_verify_command(verify_command: &str) -> Result<(), Error> {
+    println!("Running the verify command `{verify_command}`...");
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(verify_command)
+        .status()
+        .map_err(|e| {
+            Error::Other(format!(
+                "Unable to run the verify command `{verify_command}`: {e}"
+            ))
+        })?;
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "The verify command `{verify_command}` failed with {status}!"
+        )));
+    }
+
+    Ok(())
+}
+
+/// How far publishing got before it stopped
+#[derive(Debug, Default)]
+struct PublishProgress {
+    /// Any remote has received part of the release
+    published: bool,
+    /// A push failed because git couldn't authenticate with the remote
+    auth_failed: bool,
+}
+
+/// Verifies the tag if requested and pushes the release, recording the
+/// progress as it goes
+fn publish(
+    config: &Config,
+    args: &PushArgs,
+    tag: &str,
+    remotes: &[String],
+    progress: &mut PublishProgress,
+) -> Result<(), Error> {
+    if args.verify_signature {
+        verify_tag_signature(config, tag)?;
+    }
+
+    if remotes.is_empty() {
+        if !args.tag_only {
+            let outcome = run_push(&mut push_command(args.no_verify))?;
+            if outcome != PushOutcome::Pushed {
+                progress.auth_failed = outcome == PushOutcome::AuthFailed;
+                return Err(push_error("Failed to push the release!", outcome));
+            }
+            progress.published = true;
+        }
+        if args.no_tag {
+            println!("Pushed the release commit. No tag was created.");
+            return Ok(());
+        }
+        let outcome = run_push(push_command(args.no_verify).arg("--tags"))?;
+        if outcome != PushOutcome::Pushed {
+            progress.auth_failed = outcome == PushOutcome::AuthFailed;
+            return Err(push_error("Failed to push the tag!", outcome));
+        }
+        return Ok(());
+    }
+
+    let mut pushed_remotes: Vec<&str> = Vec::new();
+    for remote in remotes {
+        let outcome = push_to_remote(remote, args)?;
+        if outcome != PushOutcome::Pushed {
+            if !pushed_remotes.is_empty() {
+                println!(
+                    "The release was already pushed to: {}",
+                    pushed_remotes.join(", ")
+                );
+            }
+            progress.auth_failed = outcome == PushOutcome::AuthFailed;
+            return Err(push_error(
+                &format!("Failed to push the release to {remote}!"),
+                outcome,
+            ));
+        }
+        pushed_remotes.push(remote);
+        progress.published = true;
+    }
+
+    if args.no_tag {
+        println!(
+            "Pushed the release commit to: {}. No tag was created.",
+            pushed_remotes.join(", ")
+        );
+    } else {
+        println!("Pushed the release to: {}", pushed_remotes.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Deletes the tag and undoes the commit created by this run, keeping the
+/// release changes staged as they were before `push`
+fn roll_back(tag: &str, created_tag: bool, created_commit_parent: Option<&str>) {
+    if created_tag
+        && !traced_status(git::command().arg("tag").arg("-d").arg(tag))
+            .is_ok_and(|status| status.success())
+    {
+        println!("Failed to delete the tag {tag} while rolling back!");
+        return;
+    }
+
+    if let Some(parent) = created_commit_parent {
+        if !traced_status(git::command().arg("reset").arg("--soft").arg(parent))
+            .is_ok_and(|status| status.success())
+        {
+            println!("Failed to undo the release commit while rolling back!");
+            return;
+        }
+    }
+
+    println!("Rolled back the release commit and tag created by this run.");
+}
+
+fn create_tag(
+    config: &Config,
+    tag: &str,
+    tag_message_file: Option<&Path>,
+    signing_key: Option<&str>,
+    tagger: Option<&Identity>,
+    commit_message: &str,
+    template_values: &[(&str, &str)],
+) -> Result<(), Error> {
+    let mut tag_command = signing_command(config, signing_key.is_some());
+    set_committer(&mut tag_command, tagger);
+    tag_command.arg("tag");
+    match (tag_message_file, &config.tag_message, signing_key) {
+        (Some(tag_message_file), _, _) => {
+            tag_command.arg("-a").arg("-F").arg(tag_message_file);
+        }
+        (None, Some(tag_message), _) => {
+            tag_command
+                .arg("-a")
+                .arg("-m")
+                .arg(render_template(tag_message, template_values));
+        }
+        // Signed tags are always annotated, so they need a message
+        (None, None, Some(_)) => {
+            tag_command.arg("-m").arg(commit_message);
+        }
+        (None, None, None) => {}
+    }
+    if let Some(signing_key) = signing_key {
+        tag_command.arg("-s").arg("-u").arg(signing_key);
+    }
+    run_git(
+        tag_command.arg(tag),
+        &format!("Failed to create the tag {tag}!"),
+    )?;
+
+    Ok(())
+}
+
+/// Checks the tag's signature, so an unverifiable tag is never pushed
+fn verify_tag_signature(config: &Config, tag: &str) -> Result<(), Error> {
+    let output = query_git(
+        signing_command(config, true).arg("tag").arg("-v").arg(tag),
+        &format!("Failed to verify the signature of the tag {tag}!"),
+    )?;
+
+    if !output.status.success() {
+        return Err(Error::Git(format!(
+            "The signature of the tag {tag} doesn't verify! Nothing was pushed.\n{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+
+    println!("Verified the signature of the tag {tag}.");
+    Ok(())
+}
+
+/// Hash of the HEAD commit, if there is one
+fn get_head_hash() -> Result<Option<String>, Error> {
+    let output = query_git(
+        git::command()
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg("--quiet")
+            .arg("HEAD"),
+        "Failed to read the HEAD commit!",
+    )?;
+
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// Subject of the HEAD commit, if there is one
+fn get_head_subject() -> Result<Option<String>, Error> {
+    let output = query_git(
+        git::command().arg("log").arg("-1").arg("--format=%s"),
+        "Failed to read the HEAD commit!",
+    )?;
+
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+fn tag_points_at_head(tag: &str) -> bool {
+    let rev_parse = |revision: String| {
+        run_git(
+            git::command().arg("rev-parse").arg(&revision),
+            &format!("Failed to resolve {revision}!"),
+        )
+        .ok()
+        .map(|output| output.stdout)
+    };
+
+    // Without a HEAD commit the tag can't point at it
+    match (
+        rev_parse(format!("{tag}^{{commit}}")),
+        rev_parse(String::from("HEAD")),
+    ) {
+        (Some(tag_commit), Some(head)) => tag_commit == head,
+        _ => false,
+    }
+}
+
+/// Pushes the current branch and tags to the remote, stopping at the first failure
+fn push_to_remote(remote: &str, args: &PushArgs) -> Result<PushOutcome, Error> {
+    if !args.tag_only {
+        let outcome = run_push(push_command(args.no_verify).arg(remote).arg("HEAD"))?;
+        if outcome != PushOutcome::Pushed || args.no_tag {
+            return Ok(outcome);
+        }
+    }
+
+    run_push(push_command(args.no_verify).arg(remote).arg("--tags"))
+}
+
+/// How a `git push` went
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PushOutcome {
+    Pushed,
+    Failed,
+    /// Git couldn't authenticate with the remote
+    AuthFailed,
+}
+
+/// Runs the push, passing its output through, and tells authentication
+/// failures from other ones by git's error message
+fn run_push(command: &mut Command) -> Result<PushOutcome, Error> {
+    let output = traced_output(command).map_err(|e| {
+        Error::Git(format!(
+            "Failed to push! Unable to run {}: {e}",
+            git::program()
+        ))
+    })?;
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+    if output.status.success() {
+        return Ok(PushOutcome::Pushed);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+    let is_auth_failure = [
+        "permission denied",
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "invalid username or password",
+        "terminal prompts disabled",
+        "the requested url returned error: 403",
+    ]
+    .iter()
+    .any(|message| stderr.contains(message));
+
+    if is_auth_failure {
+        Ok(PushOutcome::AuthFailed)
+    } else {
+        Ok(PushOutcome::Failed)
+    }
+}
+
+/// The error for a failed push, with advice on setting up credentials when
+/// git couldn't authenticate
+fn push_error(message: &str, outcome: PushOutcome) -> Error {
+    if outcome != PushOutcome::AuthFailed {
+        return Error::Git(message.to_string());
+    }
+
+    Error::Git(format!(
+        "{message} Git couldn't authenticate with the remote. For SSH remotes, check that \
+your key is loaded with `ssh-add -l` and added to your GitHub account. For HTTPS remotes, set \
+up a credential helper or a personal access token, e.g. with `gh auth setup-git`."
+    ))
+}
+
+/// A `git push` command, skipping the pre-push hook if requested
+fn push_command(no_verify: bool) -> Command {
+    let mut command = git::command();
+    command.arg("push");
+    if no_verify {
+        command.arg("--no-verify");
+    }
+    command
+}
+
+fn remote_exists(remote: &str) -> Result<bool, Error> {
+    let output = query_git(
+        git::command().arg("remote").arg("get-url").arg(remote),
+        &format!("Failed to look up the remote {remote}!"),
+    )?;
+
+    Ok(output.status.success())
+}
+
+/// Key to sign with, either from the config or git's own user.signingkey
+fn get_signing_key(config: &Config) -> Result<Option<String>, Error> {
+    if let Some(signing_key) = &config.signing_key {
+        return Ok(Some(signing_key.clone()));
+    }
+
+    let output = query_git(
+        git::command().arg("config").arg("user.signingkey"),
+        "Failed to read git's user.signingkey!",
+    )?;
+
+    let signing_key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((output.status.success() && !signing_key.is_empty()).then_some(signing_key))
+}
+
+/// A git command with the configured GPG program applied when signing
+fn signing_command(config: &Config, sign: bool) -> Command {
+    let mut command = git::command();
+    if let (true, Some(gpg_program)) = (sign, &config.gpg_program) {
+        command.arg("-c").arg(format!("gpg.program={gpg_program}"));
+    }
+    command
+}
+
+/// Makes the git command commit or tag as the identity, if given, for this
+/// command only. Must be called before the subcommand is added.
+fn set_committer(command: &mut Command, committer: Option<&Identity>) {
+    if let Some(committer) = committer {
+        command
+            .arg("-c")
+            .arg(format!("user.name={}", committer.name))
+            .arg("-c")
+            .arg(format!("user.email={}", committer.email));
+    }
+}
+
+fn head_is_on_branch() -> Result<bool, Error> {
+    let output = query_git(
+        git::command()
+            .arg("symbolic-ref")
+            .arg("--quiet")
+            .arg("HEAD"),
+        "Failed to read the current branch!",
+    )?;
+
+    Ok(output.status.success())
+}
+
+/// The files `push` stages itself: those `generate` writes for the version,
+/// followed by the configured extra files
+fn release_files(config: &Config, version: &str) -> Vec<PathBuf> {
+    let mut files = vec![
+        PathBuf::from(CHANGELOG_FILE_NAME),
+        PathBuf::from(VERSION_FILE_NAME),
+    ];
+    if let Some(template) = &config.release_file {
+        files.push(PathBuf::from(render_template(
+            template,
+            &[("version", version)],
+        )));
+    }
+    files.extend(config.extra_staged_files.iter().map(PathBuf::from));
+
+    files
+}
+
+/// Stages the files `generate` wrote, or every change with `commit_all`
+fn stage_release_files(config: &Config, version: &str, commit_all: bool) -> Result<(), Error> {
+    let mut add_command = git::command();
+    add_command.arg("add");
+    if commit_all {
+        add_command.arg("--all");
+    } else {
+        add_command.arg("--");
+        for file in release_files(config, version) {
+            let is_extra = config
+                .extra_staged_files
+                .iter()
+                .any(|extra| Path::new(extra) == file);
+            if is_extra && !has_changes(&file)? {
+                println!(
+                    "Warning: {} is listed in extra_staged_files but has no changes.",
+                    file.display()
+                );
+            } else if is_extra || file.exists() {
+                add_command.arg(file);
+            }
+        }
+    }
+    run_git(&mut add_command, "Failed to stage the release changes!")?;
+
+    Ok(())
+}
+
+/// Whether the file has changes from HEAD, staged or not, or is untracked
+fn has_changes(file: &Path) -> Result<bool, Error> {
+    let output = run_git(
+        git::command()
+            .arg("status")
+            .arg("--porcelain")
+            .arg("--")
+            .arg(file),
+        &format!("Failed to get the status of {}!", file.display()),
+    )?;
+
+    Ok(!output.stdout.is_empty())
+}
+
+/// Whether tracked files other than the release files have changes that
+/// aren't staged for the release commit
+fn has_unstaged_changes(config: &Config, version: &str) -> Result<bool, Error> {
+    let output = query_git(
+        git::command()
+            .arg("diff")
+            .arg("--quiet")
+            .arg("--")
+            .arg(".")
+            .args(
+                release_files(config, version)
+                    .iter()
+                    .map(|file| format!(":(exclude){}", file.display())),
+            ),
+        "Failed to get the status of the working tree!",
+    )?;
+
+    Ok(!output.status.success())
+}
+
+/// Whether the index has changes for the next commit
+fn has_staged_changes() -> Result<bool, Error> {
+    let output = query_git(
+        git::command().arg("diff").arg("--cached").arg("--quiet"),
+        "Failed to check the staged changes!",
+    )?;
+
+    // `--quiet` exits with 1 when there are changes
+    match output.status.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err(Error::Git(format!(
+            "Unable to check the staged changes: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+fn changelog_has_uncommitted_changes() -> Result<bool, Error> {
+    let output = run_git(
+        git::command()
+            .arg("status")
+            .arg("--porcelain")
+            .arg("--")
+            .arg(CHANGELOG_FILE_NAME),
+        "Failed to get the status of the changelog file!",
+    )?;
+
+    Ok(!output.stdout.is_empty())
+}
+
+fn ref_exists(git_ref: &str) -> Result<bool, Error> {
+    let output = query_git(
+        git::command()
+            .arg("rev-parse")
+            .arg("--quiet")
+            .arg("--verify")
+            .arg(format!("{git_ref}^{{commit}}")),
+        &format!("Failed to look up the ref {git_ref}!"),
+    )?;
+
+    Ok(output.status.success())
+}
+
+fn tag_exists(tag: &str) -> Result<bool, Error> {
+    let output = query_git(
+        git::command()
+            .arg("rev-parse")
+            .arg("--quiet")
+            .arg("--verify")
+            .arg(format!("refs/tags/{tag}")),
+        &format!("Failed to look up the tag {tag}!"),
+    )?;
+
+    Ok(output.status.success())
+}
+
+fn get_changes(version: &str) -> Result<String, String> {
+    let changelog_content = read_file(Path::new(CHANGELOG_FILE_NAME)).map_err(|e| e.to_string())?;
+    let lines: Vec<&str> = changelog_content.lines().collect();
+
+    debug!(version, "reading the changes");
+
+    section_changes(&lines, version)
+}
+
+/// The lines of the version's section below its heading, without subsection headings
+fn section_changes(lines: &[&str], version: &str) -> Result<String, String> {
+    let sections = parse_sections(lines);
+    let Some(section) = find_section(&sections, version) else {
+        return Err(format!(
+            "Unable to find the section for {version} in the changelog! Searched for a line \
+starting with `# [{version}]`. Does version.txt match the changelog?"
+        ));
+    };
+
+    // The changes end at the first link definition, or else at the next
+    // section or the end of the file, so a missing link line never pulls in
+    // the sections below
+    let body_end = section_body_end(lines, section);
+
+    let mut extracted_changes = String::new();
+    for line in &lines[section.heading + 1..body_end] {
+        if !line.starts_with('#') {
+            extracted_changes.push_str(line);
+            extracted_changes.push('\n');
+        }
+    }
+
+    if extracted_changes.trim().is_empty() {
+        return Err(format!(
+            "The section for {version} in the changelog is empty! Searched for changes below \
+`# [{version}]`."
+        ));
+    }
+
+    Ok(extracted_changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::links::LinkStyle;
+
+    fn commit(msg: &str) -> Commit<'_> {
+        Commit {
+            hash: "0123456789abcdef0123456789abcdef01234567",
+            author: "Test Author <author@example.com>",
+            msg,
+            wrapped: "",
+            body: "",
+        }
+    }
+
+    #[test]
+    fn version_file_skips_leading_blank_lines() {
+        assert_eq!(
+            parse_version_file("\n  \n1.2.3\n").as_deref(),
+            Some("1.2.3")
+        );
+    }
+
+    #[test]
+    fn version_file_skips_comments() {
+        let contents = "# The released version\n\n# Bumped by shipper-release\n  1.2.3  \n2.0.0\n";
+
+        assert_eq!(parse_version_file(contents).as_deref(), Some("1.2.3"));
+    }
+
+    #[test]
+    fn version_file_without_a_version() {
+        assert_eq!(parse_version_file("# Only a comment\n\n"), None);
+        assert_eq!(parse_version_file(""), None);
+    }
+
+    #[test]
+    fn version_file_keeps_its_trailing_newline() {
+        assert_eq!(version_file_contents("1.2.3\n", "1.3.0"), "1.3.0\n");
+    }
+
+    #[test]
+    fn version_file_without_a_trailing_newline() {
+        assert_eq!(version_file_contents("1.2.3", "1.3.0"), "1.3.0");
+        // A missing file gets no newline either, as before
+        assert_eq!(version_file_contents("", "1.3.0"), "1.3.0");
+    }
+
+    #[test]
+    fn version_file_keeps_its_comments_and_blank_lines() {
+        assert_eq!(
+            version_file_contents("# The current version\n\n  1.2.3  \n# Trailer\n", "1.3.0"),
+            "# The current version\n\n  1.3.0  \n# Trailer\n"
+        );
+        // Only the first version line is the version
+        assert_eq!(
+            version_file_contents("1.2.3\n1.2.3", "1.3.0"),
+            "1.3.0\n1.2.3"
+        );
+    }
+
+    #[test]
+    fn new_version_with_a_malformed_prerelease_label() {
+        assert!(check_new_version("1.3.0-rc.1").is_ok());
+        assert!(check_new_version("1.3.0-rc..1").is_err());
+        assert!(check_new_version("1.3.0-rc.01").is_err());
+        assert!(check_new_version("1.3.0-r c").is_err());
+    }
+
+    #[test]
+    fn insert_section_without_the_unreleased_link() {
+        let config = Config {
+            unreleased_heading: String::from("Upcoming"),
+            ..Config::default()
+        };
+        let changelog = [
+            "# [Unreleased]",
+            "",
+            "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.0.0...HEAD",
+        ];
+
+        let error = insert_section(
+            &config,
+            &changelog,
+            "1.1.0",
+            vec![String::from("# [1.1.0] - 2024-01-01")],
+            String::from("[1.1.0]: https://example.com"),
+            true,
+        )
+        .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("`[Upcoming]: https://github.com/shipperstack/shipper/compare/`"));
+    }
+
+    #[test]
+    fn bot_authored_commits_are_filtered_out() {
+        let commits = [
+            commit("Add a flag"),
+            Commit {
+                author: "dependabot[bot] <49699333+dependabot[bot]@users.noreply.github.com>",
+                ..commit("Bump serde from 1.0.1 to 1.0.2")
+            },
+            Commit {
+                author: "renovate-bot <bot@renovateapp.com>",
+                ..commit("Update the lockfile")
+            },
+        ];
+        let filter_args = FilterArgs {
+            exclude_author: vec![
+                String::from("dependabot[bot]"),
+                String::from(r"^renovate-bot <"),
+            ],
+            ..FilterArgs::default()
+        };
+
+        let filtered = filter_commits(&filter_args, commits.into_iter());
+
+        assert_eq!(filtered, vec![commit("Add a flag")]);
+    }
+
+    #[test]
+    fn rendering_the_same_commits_twice_is_identical() {
+        let config = Config {
+            group_by: GroupBy::Type,
+            ..Config::default()
+        };
+        let entry_args = EntryArgs {
+            sort: Some(SortOrder::Alphabetical),
+            ..EntryArgs::default()
+        };
+        let commits = vec![
+            commit("fix: update docs"),
+            commit("feat: zebra mode"),
+            commit("chore: bump deps"),
+            commit("feat: Apple mode"),
+            commit("fix: Update docs"),
+            commit("feat: apple mode"),
+        ];
+
+        let render = || {
+            render_section_body(
+                &config,
+                &entry_args,
+                commits.clone(),
+                "1.1.0",
+                "1.0.0",
+                "2024-01-01",
+            )
+        };
+        let body = render();
+
+        assert_eq!(body, render());
+        assert_eq!(
+            body,
+            [
+                "",
+                "## Features",
+                "",
+                "- Apple mode",
+                "- apple mode",
+                "- zebra mode",
+                "",
+                "## Bug Fixes",
+                "",
+                "- update docs",
+                "- Update docs",
+                "",
+                "## Other",
+                "",
+                "- chore: bump deps",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn git_log_with_abbreviated_hashes() {
+        let stdout = "abc1234\x1fA <a@example.com>\x1fAdd a flag\n\x1e\n\
+def5678\x1fB <b@example.com>\x1fFix a bug\n\nThe body\n\x1e\n";
+
+        let commits: Vec<Commit> = parse_git_log(stdout).collect();
+
+        assert_eq!(
+            commits,
+            [
+                Commit {
+                    hash: "abc1234",
+                    author: "A <a@example.com>",
+                    msg: "Add a flag",
+                    wrapped: "",
+                    body: "",
+                },
+                Commit {
+                    hash: "def5678",
+                    author: "B <b@example.com>",
+                    msg: "Fix a bug",
+                    wrapped: "",
+                    body: "The body",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn git_log_with_full_hashes() {
+        let hash = "0123456789abcdef0123456789ABCDEF01234567";
+        let stdout = format!("{hash}\x1fA <a@example.com>\x1fAdd a flag\n\x1e\n");
+
+        let commits: Vec<Commit> = parse_git_log(&stdout).collect();
+
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].hash, hash);
+        assert_eq!(commits[0].msg, "Add a flag");
+
+        // Links show the abbreviated hash but point at the full one
+        let entry_args = EntryArgs {
+            show_hash: true,
+            ..EntryArgs::default()
+        };
+        assert_eq!(
+            render_entry(&Config::default(), &entry_args, &commits[0]),
+            format!(
+                "Add a flag ([0123456](https://github.com/shipperstack/shipper/commit/{hash}))"
+            )
+        );
+    }
+
+    #[test]
+    fn git_log_without_a_valid_hash() {
+        let stdout = "abc12\x1fA <a@example.com>\x1fAdd a flag\n\x1e\n\
+not-a-hash\x1fA <a@example.com>\x1fFix a bug\n\x1e\n";
+
+        assert_eq!(parse_git_log(stdout).count(), 0);
+    }
+
+    #[test]
+    fn ticket_prefix_is_stripped() {
+        let config = Config::default();
+
+        assert_eq!(
+            strip_ticket_prefix(&config, "PROJ-123: Add a flag"),
+            "Add a flag"
+        );
+        assert_eq!(strip_ticket_prefix(&config, "AB-7 Fix a bug"), "Fix a bug");
+    }
+
+    #[test]
+    fn ticket_prefix_is_linked_with_a_ticket_url() {
+        let config = Config {
+            ticket_url: Some(String::from("https://jira.example.com/browse/")),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            strip_ticket_prefix(&config, "PROJ-123: Add a flag"),
+            "Add a flag ([PROJ-123](https://jira.example.com/browse/PROJ-123))"
+        );
+    }
+
+    #[test]
+    fn subject_without_a_ticket_prefix_is_unchanged() {
+        let config = Config {
+            ticket_url: Some(String::from("https://jira.example.com/browse/")),
+            ..Config::default()
+        };
+
+        assert_eq!(strip_ticket_prefix(&config, "Add a flag"), "Add a flag");
+        // Only a leading key is a prefix
+        assert_eq!(
+            strip_ticket_prefix(&config, "Fix PROJ-123: the crash"),
+            "Fix PROJ-123: the crash"
+        );
+    }
+
+    #[test]
+    fn insert_section_with_a_bottom_links_block() {
+        let changelog = [
+            "# Changelog",
+            "",
+            "# [Unreleased]",
+            "",
+            "",
+            "# [1.0.0] - 2024-01-01",
+            "",
+            "- First release",
+            "",
+            "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.0.0...HEAD",
+            "[1.0.0]: https://github.com/shipperstack/shipper/tree/1.0.0",
+        ];
+        let (section, link) = render_section(
+            &Config::default(),
+            &EntryArgs::default(),
+            vec![commit("Add a flag")],
+            "1.1.0",
+            "1.0.0",
+            "2024-02-01",
+        );
+
+        let new_changelog =
+            insert_section(&Config::default(), &changelog, "1.1.0", section, link, true).unwrap();
+
+        assert_eq!(
+            new_changelog,
+            [
+                "# Changelog",
+                "",
+                "# [Unreleased]",
+                "",
+                "",
+                "# [1.1.0] - 2024-02-01",
+                "",
+                "- Add a flag",
+                "",
+                "",
+                "# [1.0.0] - 2024-01-01",
+                "",
+                "- First release",
+                "",
+                "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.1.0...HEAD",
+                "[1.1.0]: https://github.com/shipperstack/shipper/compare/1.0.0...1.1.0",
+                "[1.0.0]: https://github.com/shipperstack/shipper/tree/1.0.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn short_entries_are_not_truncated() {
+        assert_eq!(truncate_entry("Add a flag", 10), "Add a flag");
+        assert_eq!(truncate_entry("Add a flag (#12)", 10), "Add a flag (#12)");
+    }
+
+    #[test]
+    fn long_entries_end_in_an_ellipsis() {
+        assert_eq!(
+            truncate_entry("Add a flag for the output format", 12),
+            "Add a flag…"
+        );
+    }
+
+    #[test]
+    fn truncation_keeps_the_reference() {
+        assert_eq!(
+            truncate_entry("Add a flag for the output format (#123)", 12),
+            "Add a flag… (#123)"
+        );
+    }
+
+    #[test]
+    fn truncation_of_a_multibyte_subject() {
+        // Each of these is several bytes, so a byte index would split one
+        assert_eq!(
+            truncate_entry("Prise en charge des émojis 🎉🎉🎉 (#7)", 30),
+            "Prise en charge des émojis 🎉🎉🎉 (#7)"
+        );
+        assert_eq!(truncate_entry("Ünïcödé sübjéct", 6), "Ünïcö…");
+        assert_eq!(truncate_entry("🎉🎉🎉🎉 (#8)", 3), "🎉🎉… (#8)");
+    }
+
+    #[test]
+    fn newest_first_flips_the_entry_order() {
+        let config = Config::default();
+        let commits = vec![
+            commit("Add a flag"),
+            commit("Fix a bug"),
+            commit("Update the docs"),
+        ];
+        let render = |newest_first: bool| {
+            let entry_args = EntryArgs {
+                newest_first,
+                ..EntryArgs::default()
+            };
+            render_section_body(
+                &config,
+                &entry_args,
+                commits.clone(),
+                "1.1.0",
+                "1.0.0",
+                "2024-01-01",
+            )
+        };
+
+        assert_eq!(
+            render(false),
+            ["", "- Add a flag", "- Fix a bug", "- Update the docs", ""]
+        );
+        assert_eq!(
+            render(true),
+            ["", "- Update the docs", "- Fix a bug", "- Add a flag", ""]
+        );
+    }
+
+    #[test]
+    fn insert_section_keeps_the_preamble() {
+        let preamble = [
+            "# Changelog",
+            "",
+            "",
+            "All notable changes are documented here.  ",
+            "The format is based on [Keep a Changelog](https://keepachangelog.com).",
+            "",
+            "---",
+            "",
+            "",
+            "",
+        ];
+        let mut changelog = preamble.to_vec();
+        changelog.extend([
+            "# [Unreleased]",
+            "",
+            "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.0.0...HEAD",
+            "",
+            "",
+            "# [1.0.0] - 2024-01-01",
+            "",
+            "- First release",
+        ]);
+        let (section, link) = render_section(
+            &Config::default(),
+            &EntryArgs::default(),
+            vec![commit("Add a flag")],
+            "1.1.0",
+            "1.0.0",
+            "2024-02-01",
+        );
+
+        let new_changelog =
+            insert_section(&Config::default(), &changelog, "1.1.0", section, link, true).unwrap();
+
+        assert_eq!(new_changelog[..preamble.len()], preamble);
+        assert_eq!(new_changelog[preamble.len()], "# [Unreleased]");
+    }
+
+    #[test]
+    fn squash_merge_is_expanded_into_its_commits() {
+        // As GitHub writes it when squash merging a pull request
+        let squash_merge = Commit {
+            body: "* Add the export command\n\n* Fix the CSV quoting\n\n\
+* Address review comments\n\n---------\n\nCo-authored-by: Reviewer <reviewer@example.com>",
+            ..commit("Add CSV export (#42)")
+        };
+        let commits = vec![commit("Fix a typo"), squash_merge];
+
+        let expanded = expand_squash_merges(commits);
+
+        assert_eq!(
+            expanded,
+            [
+                commit("Fix a typo"),
+                commit("Add the export command"),
+                commit("Fix the CSV quoting"),
+                commit("Address review comments"),
+            ]
+        );
+    }
+
+    #[test]
+    fn squash_merge_without_bullets_is_kept() {
+        let squash_merge = Commit {
+            body: "Adds the export command.",
+            ..commit("Add CSV export (#42)")
+        };
+        // Only subjects ending in a pull request reference are squash merges
+        let not_squash_merge = Commit {
+            body: "- First\n- Second",
+            ..commit("Add CSV export")
+        };
+
+        assert_eq!(
+            expand_squash_merges(vec![squash_merge, not_squash_merge]),
+            [squash_merge, not_squash_merge]
+        );
+    }
+
+    #[test]
+    fn changes_of_a_section_without_a_link_line() {
+        let changelog = [
+            "# [Unreleased]",
+            "",
+            "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.1.0...HEAD",
+            "",
+            "",
+            "# [1.1.0] - 2024-02-01",
+            "",
+            "- Second release",
+            "",
+            "",
+            "# [1.0.0] - 2024-01-01",
+            "",
+            "- First release",
+        ];
+
+        assert_eq!(
+            section_changes(&changelog, "1.1.0").unwrap(),
+            "\n- Second release\n\n\n"
+        );
+        assert_eq!(
+            section_changes(&changelog, "1.0.0").unwrap(),
+            "\n- First release\n"
+        );
+    }
+
+    #[test]
+    fn changes_of_an_empty_or_missing_section() {
+        let changelog = ["# [1.1.0] - 2024-02-01", "", "# [1.0.0] - 2024-01-01"];
+
+        assert!(section_changes(&changelog, "1.1.0").is_err());
+        assert!(section_changes(&changelog, "0.9.0").is_err());
+    }
+
+    #[test]
+    fn entries_use_the_configured_bullet() {
+        let config = Config {
+            group_by: GroupBy::Type,
+            bullet: '*',
+            ..Config::default()
+        };
+        let commits = vec![commit("feat!: drop the v1 API"), commit("fix: a crash")];
+
+        let body = render_section_body(
+            &config,
+            &EntryArgs::default(),
+            commits,
+            "2.0.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        assert_eq!(
+            body,
+            [
+                "",
+                "## ⚠ BREAKING CHANGES",
+                "",
+                "* drop the v1 API",
+                "",
+                "## Features",
+                "",
+                "* drop the v1 API",
+                "",
+                "## Bug Fixes",
+                "",
+                "* a crash",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn git_log_with_a_wrapped_subject() {
+        let stdout = "abc1234\x1fA <a@example.com>\x1fAdd an option to join subjects\n\
+  that wrap onto the next line\nlike this one\n\nThe body\n\nCloses #3\n\x1e\n";
+
+        let commits: Vec<Commit> = parse_git_log(stdout).collect();
+
+        assert_eq!(
+            commits,
+            [Commit {
+                hash: "abc1234",
+                author: "A <a@example.com>",
+                msg: "Add an option to join subjects",
+                wrapped: "that wrap onto the next line\nlike this one",
+                body: "The body\n\nCloses #3",
+            }]
+        );
+    }
+
+    #[test]
+    fn wrapped_subject_is_joined_only_when_enabled() {
+        let wrapped_commit = Commit {
+            wrapped: "that wrap onto the next line\n  like this one",
+            ..commit("Add an option to join subjects")
+        };
+        let render = |join_wrapped_subjects: bool| {
+            let entry_args = EntryArgs {
+                join_wrapped_subjects,
+                ..EntryArgs::default()
+            };
+            render_entry(&Config::default(), &entry_args, &wrapped_commit)
+        };
+
+        assert_eq!(render(false), "Add an option to join subjects");
+        assert_eq!(
+            render(true),
+            "Add an option to join subjects that wrap onto the next line like this one"
+        );
+    }
+
+    #[test]
+    fn section_grouped_by_type_with_breaking_changes() {
+        let config = Config {
+            group_by: GroupBy::Type,
+            ..Config::default()
+        };
+        let commits = vec![
+            commit("fix(cli): handle a missing config"),
+            commit("feat!: drop the v1 API"),
+            commit("chore: bump deps"),
+            commit("feat: add a flag"),
+        ];
+
+        let (section, link) = render_section(
+            &config,
+            &EntryArgs::default(),
+            commits,
+            "2.0.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        assert_eq!(
+            section,
+            [
+                "# [2.0.0] - 2024-01-01",
+                "",
+                "## ⚠ BREAKING CHANGES",
+                "",
+                "- drop the v1 API",
+                "",
+                "## Features",
+                "",
+                "- drop the v1 API",
+                "- add a flag",
+                "",
+                "## Bug Fixes",
+                "",
+                "- **cli:** handle a missing config",
+                "",
+                "## Other",
+                "",
+                "- chore: bump deps",
+                "",
+            ]
+        );
+        assert_eq!(
+            link,
+            "[2.0.0]: https://github.com/shipperstack/shipper/compare/1.0.0...2.0.0"
+        );
+    }
+
+    #[test]
+    fn section_grouped_by_verb_newest_first() {
+        let config = Config {
+            group_by: GroupBy::Verb,
+            ..Config::default()
+        };
+        let entry_args = EntryArgs {
+            newest_first: true,
+            ..EntryArgs::default()
+        };
+        let commits = vec![
+            commit("Added a flag"),
+            commit("Fix a crash"),
+            commit("Tidy up"),
+            commit("Adds a subcommand"),
+        ];
+
+        let body = render_section_body(
+            &config,
+            &entry_args,
+            commits,
+            "1.1.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        assert_eq!(
+            body,
+            [
+                "",
+                "## Added",
+                "",
+                "- Adds a subcommand",
+                "- Added a flag",
+                "",
+                "## Fixed",
+                "",
+                "- Fix a crash",
+                "",
+                "## Other",
+                "",
+                "- Tidy up",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn section_without_grouping_sorted_alphabetically() {
+        let entry_args = EntryArgs {
+            sort: Some(SortOrder::Alphabetical),
+            ..EntryArgs::default()
+        };
+        let commits = vec![
+            commit("feat!: drop the v1 API"),
+            commit("Update the docs"),
+            commit("add a flag"),
+        ];
+
+        let body = render_section_body(
+            &Config::default(),
+            &entry_args,
+            commits,
+            "2.0.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        // Without sections there is no breaking changes section either
+        assert_eq!(
+            body,
+            [
+                "",
+                "- add a flag",
+                "- feat!: drop the v1 API",
+                "- Update the docs",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn section_link_follows_the_link_style() {
+        let render_link = |link_style| {
+            let config = Config {
+                link_style,
+                tag_prefix: String::from("v"),
+                ..Config::default()
+            };
+            render_section(
+                &config,
+                &EntryArgs::default(),
+                vec![commit("Add a flag")],
+                "1.1.0",
+                "1.0.0",
+                "2024-01-01",
+            )
+            .1
+        };
+
+        assert_eq!(
+            render_link(LinkStyle::Compare),
+            "[1.1.0]: https://github.com/shipperstack/shipper/compare/v1.0.0...v1.1.0"
+        );
+        assert_eq!(
+            render_link(LinkStyle::Tree),
+            "[1.1.0]: https://github.com/shipperstack/shipper/tree/v1.1.0"
+        );
+        assert_eq!(
+            render_link(LinkStyle::Release),
+            "[1.1.0]: https://github.com/shipperstack/shipper/releases/tag/v1.1.0"
+        );
+    }
+
+    #[test]
+    fn section_entries_with_hash_and_issue_links() {
+        let config = Config {
+            issue_url_template: Some(String::from("https://issues.example.com/{number}")),
+            commit_url_template: Some(String::from("https://git.example.com/{hash}")),
+            ..Config::default()
+        };
+        let entry_args = EntryArgs {
+            show_hash: true,
+            footer_issues: true,
+            ..EntryArgs::default()
+        };
+        let commits = vec![
+            commit("Fix a crash (#12)"),
+            Commit {
+                body: "Closes #34",
+                ..commit("Add a flag")
+            },
+        ];
+
+        let body = render_section_body(
+            &config,
+            &entry_args,
+            commits,
+            "1.1.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        assert_eq!(
+            body,
+            [
+                "",
+                "- Fix a crash ([#12](https://issues.example.com/12)) \
+([0123456](https://git.example.com/0123456789abcdef0123456789abcdef01234567))",
+                "- Add a flag ([#34](https://issues.example.com/34)) \
+([0123456](https://git.example.com/0123456789abcdef0123456789abcdef01234567))",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn release_file_has_the_section_and_its_link() {
+        let changelog: Vec<String> = [
+            "# [Unreleased]",
+            "",
+            "[Unreleased]: https://github.com/shipperstack/shipper/compare/1.1.0...HEAD",
+            "",
+            "",
+            "# [1.1.0] - 2024-02-01",
+            "",
+            "- Add a flag",
+            "",
+            "[1.1.0]: https://github.com/shipperstack/shipper/compare/1.0.0...1.1.0",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(
+            release_file_contents(&changelog, "1.1.0").unwrap(),
+            "# [1.1.0] - 2024-02-01\n\n- Add a flag\n\n\
+[1.1.0]: https://github.com/shipperstack/shipper/compare/1.0.0...1.1.0\n"
+        );
+        assert!(release_file_contents(&changelog, "1.2.0").is_err());
+    }
+
+    #[test]
+    fn section_headings_with_a_configured_prefix() {
+        let config = Config {
+            group_by: GroupBy::Type,
+            section_prefixes: [(String::from("Features"), String::from("✨"))].into(),
+            ..Config::default()
+        };
+        let commits = vec![commit("feat: add a flag"), commit("fix: a crash")];
+
+        let body = render_section_body(
+            &config,
+            &EntryArgs::default(),
+            commits,
+            "1.1.0",
+            "1.0.0",
+            "2024-01-01",
+        );
+
+        // Headings without a prefix stay plain
+        assert_eq!(
+            body,
+            [
+                "",
+                "## ✨ Features",
+                "",
+                "- add a flag",
+                "",
+                "## Bug Fixes",
+                "",
+                "- a crash",
+                "",
+            ]
+        );
+    }
 }
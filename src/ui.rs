@@ -0,0 +1,102 @@
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+// Lines of unchanged context shown around a change
+const DIFF_CONTEXT: usize = 3;
+
+/// Asks a yes/no question on stdin, returning the default on an empty answer
+pub fn confirm(question: &str, default: bool) -> bool {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+    print!("{question} {choices} ");
+    io::stdout().flush().expect("Failed to flush stdout!");
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" => true,
+        _ => false,
+    }
+}
+
+/// Shows the text through the pager when stdout is a terminal, like git does,
+/// and prints it otherwise. The pager is `$PAGER` or `less`, which exits right
+/// away if the text fits on the screen.
+pub fn page(text: &str, use_pager: bool) {
+    if !use_pager || !io::stdout().is_terminal() {
+        print!("{text}");
+        return;
+    }
+
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| String::from("less"));
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    // The same defaults git gives less: quit if it fits, keep colors and the screen
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    match command.spawn() {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may be quit before reading everything
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        Err(_) => print!("{text}"),
+    }
+}
+
+/// Prints the changed region between the two versions of a file. Changes made
+/// by shipper-release are contiguous, so a single hunk covering everything
+/// between the common prefix and suffix is enough.
+pub fn print_diff<A: AsRef<str>, B: AsRef<str>>(old: &[A], new: &[B]) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a.as_ref() == b.as_ref())
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a.as_ref() == b.as_ref())
+        .count();
+
+    if prefix == old.len() && prefix == new.len() {
+        println!("(no changes)");
+        return;
+    }
+
+    let context_start = prefix.saturating_sub(DIFF_CONTEXT);
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+
+    println!(
+        "@@ -{},{} +{},{} @@",
+        context_start + 1,
+        (old_end + DIFF_CONTEXT).min(old.len()) - context_start,
+        context_start + 1,
+        (new_end + DIFF_CONTEXT).min(new.len()) - context_start,
+    );
+    for line in &old[context_start..prefix] {
+        println!(" {}", line.as_ref());
+    }
+    for line in &old[prefix..old_end] {
+        println!("-{}", line.as_ref());
+    }
+    for line in &new[prefix..new_end] {
+        println!("+{}", line.as_ref());
+    }
+    for line in &old[old_end..(old_end + DIFF_CONTEXT).min(old.len())] {
+        println!(" {}", line.as_ref());
+    }
+}
@@ -0,0 +1,101 @@
+use semver::Version;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::{today_iso8601, VERSION};
+
+// The sparse registry index entry for shipper-release
+const INDEX_URL: &str = "https://index.crates.io/sh/ip/shipper-release";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Starts checking for a newer release in the background. The receiver yields
+/// the newer version, if there is one.
+pub fn spawn_check() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        if let Some(latest) = latest_version() {
+            let is_newer = match (Version::parse(&latest), Version::parse(VERSION)) {
+                (Ok(latest), Ok(current)) => latest > current,
+                _ => false,
+            };
+            if is_newer {
+                // The receiver may already be gone if the command finished first
+                let _ = sender.send(latest);
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Prints a notice if the background check found a newer release. Waits only
+/// briefly so a slow network never holds up the command.
+pub fn print_notice(receiver: &Receiver<String>) {
+    if let Ok(latest) = receiver.recv_timeout(Duration::from_millis(250)) {
+        println!(
+            "A new version of shipper-release is available: {VERSION} -> {latest}. \
+Run `cargo install shipper-release` to update."
+        );
+    }
+}
+
+/// Latest version from the cache if it was checked today, or else from crates.io
+fn latest_version() -> Option<String> {
+    let cache_file = cache_file();
+    let today = today_iso8601();
+
+    if let Some(cached) = cache_file
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+    {
+        if let Some((date, version)) = cached.trim().split_once('\n') {
+            if date == today {
+                return Some(version.to_string());
+            }
+        }
+    }
+
+    // Failed checks are cached too, so an unreachable registry is only tried once a day
+    let latest = fetch_latest_version().unwrap_or_else(|| VERSION.to_string());
+
+    if let Some(cache_file) = cache_file {
+        if let Some(parent) = cache_file.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(cache_file, format!("{today}\n{latest}"));
+    }
+
+    Some(latest)
+}
+
+fn fetch_latest_version() -> Option<String> {
+    let index = ureq::get(INDEX_URL)
+        .timeout(REQUEST_TIMEOUT)
+        .call()
+        .ok()?
+        .into_string()
+        .ok()?;
+
+    // Each line of the index describes one published version
+    index
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|entry| entry["yanked"] != true)
+        .filter_map(|entry| Version::parse(entry["vers"].as_str()?).ok())
+        .filter(|version| version.pre.is_empty())
+        .max()
+        .map(|version| version.to_string())
+}
+
+fn cache_file() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_dir.join("shipper-release").join("latest-version"))
+}
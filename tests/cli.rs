@@ -0,0 +1,545 @@
+//! Runs shipper-release against scratch git repositories
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static REPOSITORY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A git repository with a changelog and a version file for a tagged release,
+/// removed again when dropped
+struct Repository {
+    path: PathBuf,
+}
+
+impl Repository {
+    fn new(version: &str) -> Self {
+        let path = env::temp_dir().join(format!(
+            "shipper-release-test-{}-{}",
+            std::process::id(),
+            REPOSITORY_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        let repository = Repository { path };
+        repository.git(&["init", "--quiet", "--initial-branch=main"]);
+        repository.write(
+            "CHANGELOG.md",
+            &format!(
+                "# Changelog\n\
+\n\
+# [Unreleased]\n\
+\n\
+[Unreleased]: https://github.com/shipperstack/shipper/compare/{version}...HEAD\n\
+\n\
+\n\
+# [{version}] - 2024-01-01\n\
+\n\
+- Initial release\n\
+\n\
+[{version}]: https://github.com/shipperstack/shipper/tree/{version}\n"
+            ),
+        );
+        repository.write("version.txt", &format!("{version}\n"));
+        repository.git(&["add", "."]);
+        repository.commit(&format!("release: {version}"));
+        repository.git(&["tag", version]);
+
+        repository
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        fs::write(self.path.join(file), contents).unwrap();
+    }
+
+    fn read(&self, file: &str) -> String {
+        fs::read_to_string(self.path.join(file)).unwrap()
+    }
+
+    /// A git command in the repository, isolated from the user's git config
+    fn git_command(&self) -> Command {
+        let mut command = Command::new("git");
+        command
+            .current_dir(&self.path)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "Test Author")
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_COMMITTER_NAME", "Test Committer")
+            .env("GIT_COMMITTER_EMAIL", "committer@example.com");
+        command
+    }
+
+    fn git(&self, args: &[&str]) -> Output {
+        let output = self.git_command().args(args).output().unwrap();
+        assert!(
+            output.status.success(),
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    /// Makes an empty commit with the message
+    fn commit(&self, message: &str) {
+        self.git(&["commit", "--quiet", "--allow-empty", "-m", message]);
+    }
+
+    /// Makes an empty commit with the message's raw bytes. `git commit` would
+    /// convert invalid UTF-8, so the commit object is written directly.
+    fn commit_bytes(&self, message: &[u8]) {
+        let head = stdout(&self.git(&["rev-parse", "HEAD"]));
+        let tree = stdout(&self.git(&["rev-parse", "HEAD^{tree}"]));
+        let mut object = format!(
+            "tree {}\nparent {}\nauthor Test Author <author@example.com> 1700000000 +0000\n\
+committer Test Committer <committer@example.com> 1700000000 +0000\n\n",
+            tree.trim(),
+            head.trim()
+        )
+        .into_bytes();
+        object.extend_from_slice(message);
+
+        let path = self.path.join(".git").join("TEST_COMMIT");
+        fs::write(&path, object).unwrap();
+        let commit =
+            stdout(&self.git(&["hash-object", "-t", "commit", "-w", path.to_str().unwrap()]));
+        self.git(&["update-ref", "HEAD", commit.trim()]);
+    }
+
+    /// Generates the release with the bump, then commits and tags it like `push`
+    /// would, without a remote. Returns the new version.
+    fn release(&self, bump: &str) -> String {
+        let output = self.run(&["generate", "--bump", bump, "--yes"]);
+        assert!(output.status.success(), "{}", stdout(&output));
+
+        let version = self.read("version.txt").trim().to_string();
+        self.git(&["add", "--all"]);
+        self.commit(&format!("release: {version}"));
+        self.git(&["tag", &version]);
+        version
+    }
+
+    /// A shipper-release command in the repository
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_shipper-release"));
+        command
+            .arg("-C")
+            .arg(&self.path)
+            .args(args)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env_remove("SHIPPER_RELEASE_REPO")
+            .env_remove("SHIPPER_RELEASE_TAG_PREFIX");
+        command
+    }
+
+    /// Runs shipper-release in the repository
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+}
+
+impl Drop for Repository {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn notes_with_a_commit_message_that_is_not_utf8() {
+    let repository = Repository::new("0.1.0");
+    repository.commit_bytes(b"Fix the caf\xe9 menu\n");
+    repository.commit("Add a tea menu");
+
+    let output = repository.run(&["notes", "--from", "0.1.0", "--to", "HEAD"]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        stdout(&output),
+        "- Fix the caf\u{fffd} menu\n- Add a tea menu\n"
+    );
+}
+
+#[test]
+fn generate_without_commits_since_the_release() {
+    let repository = Repository::new("0.1.0");
+    let changelog = repository.read("CHANGELOG.md");
+
+    let output = repository.run(&["generate", "--bump", "patch", "--yes"]);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        stdout(&output),
+        "No commits since 0.1.0; nothing to release.\n"
+    );
+    assert_eq!(repository.read("CHANGELOG.md"), changelog);
+    assert_eq!(repository.read("version.txt"), "0.1.0\n");
+}
+
+#[test]
+fn print_commit_message_prints_only_the_message() {
+    let repository = Repository::new("0.1.0");
+    repository.commit("Add a flag");
+    let generate = repository.run(&["generate", "--bump", "minor", "--yes"]);
+    assert!(generate.status.success(), "{}", stdout(&generate));
+
+    let output = repository.run(&["push", "--print-commit-message"]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(stdout(&output), "release: 0.2.0\n\n\n- Add a flag\n\n\n");
+}
+
+/// The section of the version in the dry run's proposed changelog
+fn proposed_section(output: &Output, version: &str) -> String {
+    let stdout = stdout(output);
+    let start = stdout
+        .find(&format!("# [{version}]"))
+        .unwrap_or_else(|| panic!("No section for {version} in:\n{stdout}"));
+    let end = stdout[start..]
+        .find(&format!("[{version}]: "))
+        .map_or(stdout.len(), |end| start + end);
+    stdout[start..end].trim().to_string()
+}
+
+#[test]
+fn finalizing_lists_the_commits_since_the_last_prerelease() {
+    let repository = Repository::new("1.0.0");
+    repository.commit("Add a flag");
+    assert_eq!(repository.release("rc"), "1.0.1-rc.1");
+    repository.commit("Fix a bug");
+
+    let output = repository.run(&[
+        "generate",
+        "--bump",
+        "patch",
+        "--dry-run",
+        "--date",
+        "2024-03-01",
+    ]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        proposed_section(&output, "1.0.1"),
+        "# [1.0.1] - 2024-03-01\n\n- Fix a bug"
+    );
+    assert!(stdout(&output)
+        .contains("[1.0.1]: https://github.com/shipperstack/shipper/compare/1.0.1-rc.1...1.0.1"));
+}
+
+#[test]
+fn finalizing_since_the_last_release() {
+    let repository = Repository::new("1.0.0");
+    repository.write(
+        "shipper-release.toml",
+        "finalize_notes_since = \"release\"\n",
+    );
+    repository.commit("Add a flag");
+    assert_eq!(repository.release("rc"), "1.0.1-rc.1");
+    repository.commit("Fix a bug");
+    assert_eq!(repository.release("rc"), "1.0.1-rc.2");
+    repository.commit("Update the docs");
+
+    let output = repository.run(&[
+        "generate",
+        "--bump",
+        "patch",
+        "--dry-run",
+        "--date",
+        "2024-03-01",
+    ]);
+
+    // The prereleases' own release commits aren't listed
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        proposed_section(&output, "1.0.1"),
+        "# [1.0.1] - 2024-03-01\n\n- Add a flag\n- Fix a bug\n- Update the docs"
+    );
+    assert!(stdout(&output)
+        .contains("[1.0.1]: https://github.com/shipperstack/shipper/compare/1.0.0...1.0.1"));
+}
+
+#[test]
+fn appended_entries_use_the_configured_bullet() {
+    let repository = Repository::new("1.0.0");
+    repository.write("shipper-release.toml", "bullet = \"*\"\n");
+    repository.commit("Add a flag");
+
+    let output = repository.run(&[
+        "generate",
+        "--bump",
+        "minor",
+        "--dry-run",
+        "--date",
+        "2024-03-01",
+        "--append-entry",
+        "- Document the flag",
+        "--append-entry",
+        "Thank the contributors",
+    ]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        proposed_section(&output, "1.1.0"),
+        "# [1.1.0] - 2024-03-01\n\n* Add a flag\n* Document the flag\n* Thank the contributors"
+    );
+}
+
+#[test]
+fn rerunning_generate_on_the_same_day_merges_the_entries() {
+    let repository = Repository::new("0.1.0");
+    repository.commit("Add a flag");
+    let first = repository.run(&[
+        "generate",
+        "--bump",
+        "minor",
+        "--yes",
+        "--date",
+        "2024-03-01",
+    ]);
+    assert!(first.status.success(), "{}", stdout(&first));
+
+    // As if the release was generated too early and version.txt was reset
+    repository.write("version.txt", "0.1.0\n");
+    repository.commit("Fix a bug");
+    let second = repository.run(&[
+        "generate",
+        "--bump",
+        "minor",
+        "--yes",
+        "--date",
+        "2024-03-01",
+    ]);
+
+    assert!(second.status.success(), "{}", stdout(&second));
+    let changelog = repository.read("CHANGELOG.md");
+    assert_eq!(changelog.matches("# [0.2.0]").count(), 1, "{changelog}");
+    assert!(
+        changelog.contains("# [0.2.0] - 2024-03-01\n\n- Add a flag\n- Fix a bug\n\n[0.2.0]: "),
+        "{changelog}"
+    );
+    assert_eq!(repository.read("version.txt"), "0.2.0\n");
+}
+
+#[test]
+fn generate_fills_in_a_planned_section() {
+    let repository = Repository::new("0.1.0");
+    let plan = repository.run(&["plan", "0.2.0"]);
+    assert!(plan.status.success(), "{}", stdout(&plan));
+    repository.commit("Add a flag");
+
+    let output = repository.run(&[
+        "generate",
+        "--bump",
+        "minor",
+        "--yes",
+        "--date",
+        "2024-03-01",
+    ]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        repository.read("CHANGELOG.md"),
+        "# Changelog\n\
+\n\
+# [Unreleased]\n\
+\n\
+[Unreleased]: https://github.com/shipperstack/shipper/compare/0.2.0...HEAD\n\
+\n\
+\n\
+# [0.2.0] - 2024-03-01\n\
+\n\
+- Add a flag\n\
+\n\
+[0.2.0]: https://github.com/shipperstack/shipper/compare/0.1.0...0.2.0\n\
+\n\
+\n\
+# [0.1.0] - 2024-01-01\n\
+\n\
+- Initial release\n\
+\n\
+[0.1.0]: https://github.com/shipperstack/shipper/tree/0.1.0\n"
+    );
+}
+
+#[test]
+fn notes_with_a_wrapped_subject() {
+    let repository = Repository::new("0.1.0");
+    repository.commit("Add an option to join subjects\nthat wrap onto the next line\n\nThe body");
+
+    let first_line = repository.run(&["notes", "--from", "0.1.0", "--to", "HEAD"]);
+    let joined = repository.run(&[
+        "notes",
+        "--from",
+        "0.1.0",
+        "--to",
+        "HEAD",
+        "--join-wrapped-subjects",
+    ]);
+
+    assert_eq!(stdout(&first_line), "- Add an option to join subjects\n");
+    assert_eq!(
+        stdout(&joined),
+        "- Add an option to join subjects that wrap onto the next line\n"
+    );
+}
+
+#[test]
+fn notes_with_a_missing_ref() {
+    let repository = Repository::new("1.0.0");
+    repository.commit("Add a flag");
+
+    let output = repository.run(&["notes", "--from", "0.9.0", "--to", "HEAD"]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(stdout(&output), "The ref 0.9.0 doesn't exist!\n");
+    // Looking the ref up isn't a failing git command, so nothing is logged
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn commit_range_file_dash_prints_the_record() {
+    let repository = Repository::new("1.0.0");
+    repository.commit("Add a flag");
+
+    let output = repository.run(&[
+        "generate",
+        "--bump",
+        "minor",
+        "--yes",
+        "--commit-range-file",
+        "-",
+    ]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("\"from\": \"1.0.0\""));
+    assert!(!stdout(&output).contains("Commit range written to"));
+    assert!(!repository.path.join("-").exists());
+}
+
+#[test]
+fn generate_keeps_the_comments_in_the_version_file() {
+    let repository = Repository::new("1.0.0");
+    repository.write("version.txt", "# The current version\n\n1.0.0\n");
+    repository.git(&[
+        "commit",
+        "--quiet",
+        "--all",
+        "-m",
+        "Document the version file",
+    ]);
+
+    let output = repository.run(&["generate", "--bump", "minor", "--yes"]);
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        repository.read("version.txt"),
+        "# The current version\n\n1.1.0\n"
+    );
+}
+
+#[test]
+fn amend_date_keeps_a_planned_section() {
+    let repository = Repository::new("0.1.0");
+    let plan = repository.run(&["plan", "0.2.0"]);
+    assert!(plan.status.success(), "{}", stdout(&plan));
+    let planned = repository.read("CHANGELOG.md");
+    assert!(planned.contains("# [0.2.0] - TBD\n"), "{planned}");
+
+    let output = repository.run(&["amend-date"]);
+
+    // The release below the planned section is the one that gets dated
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        stdout(&output),
+        format!("Updated the date of 0.1.0 to {today}.\n")
+    );
+    assert_eq!(
+        repository.read("CHANGELOG.md"),
+        planned.replace("# [0.1.0] - 2024-01-01", &format!("# [0.1.0] - {today}"))
+    );
+}
+
+#[test]
+fn amend_date_with_an_unreadable_changelog() {
+    let repository = Repository::new("1.0.0");
+    fs::remove_file(repository.path.join("CHANGELOG.md")).unwrap();
+    fs::create_dir(repository.path.join("CHANGELOG.md")).unwrap();
+
+    let output = repository.run(&["amend-date"]);
+
+    // An I/O error is a regular error rather than a panic
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).starts_with("Unable to read CHANGELOG.md: "),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn push_rolls_back_when_the_verify_command_cannot_run() {
+    let repository = Repository::new("1.0.0");
+    repository.commit("Add a flag");
+    let generate = repository.run(&["generate", "--bump", "minor", "--yes"]);
+    assert!(generate.status.success(), "{}", stdout(&generate));
+    let head = stdout(&repository.git(&["rev-parse", "HEAD"]));
+    let exec_path = stdout(&repository.git(&["--exec-path"]));
+    let git = PathBuf::from(exec_path.trim()).join("git");
+
+    // Without a PATH the shell for the verify command can't be found
+    let output = repository
+        .command(&[
+            "--git-path",
+            git.to_str().unwrap(),
+            "push",
+            "--verify",
+            "true",
+            "--rollback-on-failure",
+        ])
+        .env("PATH", "")
+        .env("GIT_AUTHOR_NAME", "Test Author")
+        .env("GIT_AUTHOR_EMAIL", "author@example.com")
+        .env("GIT_COMMITTER_NAME", "Test Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Rolled back the release commit and tag created by this run."));
+    assert!(stdout(&output).contains("Unable to run the verify command `true`: "));
+    assert_eq!(stdout(&repository.git(&["rev-parse", "HEAD"])), head);
+    assert!(repository
+        .git(&["tag", "--list", "1.1.0"])
+        .stdout
+        .is_empty());
+}
+
+#[test]
+fn release_warns_about_uncommitted_changelog_edits() {
+    let repository = Repository::new("1.0.0");
+    repository.commit("Add a flag");
+    let changelog = repository.read("CHANGELOG.md").replace("Initial", "First");
+    repository.write("CHANGELOG.md", &changelog);
+
+    // Without --yes the warning asks, and no answer keeps the files
+    let output = repository
+        .command(&["release", "--bump", "minor"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Warning: CHANGELOG.md has uncommitted changes"));
+    assert!(stdout(&output).contains("Aborted. No files were changed."));
+    assert_eq!(repository.read("CHANGELOG.md"), changelog);
+    assert_eq!(repository.read("version.txt"), "1.0.0\n");
+}